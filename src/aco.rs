@@ -0,0 +1,508 @@
+extern crate rand;
+extern crate rayon;
+
+use std::u64;
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use self::rand::Rng;
+use self::rayon::prelude::*;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use construcao;
+use diagnostico;
+use erro::ErroConstrucao;
+
+// Feromônio de cada aresta, como matriz completa
+pub type Feromonio = Vec<Vec<f64>>;
+
+// Probabilidade de a formiga do MAX-MIN reconstruir o melhor tour quando o
+// feromônio converge, usada para calcular o limite inferior
+const P_MELHOR_MMAS: f64 = 0.05;
+
+// A cada tantas iterações, o MAX-MIN deposita com o melhor tour global em vez
+// do melhor da iteração
+const INTERVALO_MELHOR_GLOBAL: u64 = 10;
+
+// Visibilidade das arestas de peso zero
+const DISTANCIA_MINIMA: f64 = 1e-6;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variante {
+    // Todas as formigas depositam feromônio, proporcional à qualidade do tour
+    AntSystem,
+    // Escolha gulosa com probabilidade q0, atualização local em cada aresta
+    // assim que uma formiga passa por ela e depósito só no melhor tour global.
+    // As formigas são construídas uma após a outra, não em paralelo.
+    AntColonySystem,
+    // Só o melhor tour deposita, e o feromônio fica entre dois limites
+    MaxMin,
+}
+
+// Próximo vértice, com probabilidade proporcional ao peso das arestas; se
+// nenhuma aresta finita leva a um vértice aberto, sorteia entre eles
+fn roleta<R: Rng>(rng: &mut R, abertos: &[(Vertice, f64)]) -> Vertice {
+    let total = abertos.iter().map(|&(_, peso)| peso).sum::<f64>();
+    if !(total > 0.0) || !total.is_finite() {
+        return abertos[rng.gen_range(0, abertos.len())].0;
+    }
+    let mut x = rng.gen::<f64>() * total;
+    for &(vertice, peso) in abertos {
+        if x < peso {
+            return vertice;
+        }
+        x -= peso;
+    }
+    abertos[abertos.len() - 1].0
+}
+
+// Próximo vértice entre os abertos. `pesos` é o peso τ^α η^β de cada aresta
+// que sai do atual; com `q0`, a formiga toma a de maior peso com essa
+// probabilidade.
+fn escolhe_proximo<R>(rng: &mut R, pesos: &[f64], visitado: &[bool], q0: Option<f64>) -> Vertice
+    where R: Rng
+{
+    let abertos = (0..pesos.len())
+        .filter(|&j| !visitado[j])
+        .map(|j| (j, pesos[j]))
+        .collect::<Vec<_>>();
+    match q0 {
+        Some(q0) if rng.gen::<f64>() < q0 => {
+            abertos.iter()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap()
+                .0
+        }
+        _ => roleta(rng, &abertos),
+    }
+}
+
+// Tour de uma formiga, com os pesos `escolha` fixos durante a construção
+fn formiga<G: Instancia>(grafo: &G, escolha: &[Vec<f64>]) -> Solucao<G::Peso> {
+    let mut rng = rand::thread_rng();
+    let n = grafo.num_vertices();
+    let mut visitado = vec![false; n];
+    let mut caminho = Vec::with_capacity(n);
+    let inicial = rng.gen_range(0, n);
+    caminho.push(inicial);
+    visitado[inicial] = true;
+
+    for _ in 1..n {
+        let atual = caminho[caminho.len() - 1];
+        let proximo = escolhe_proximo(&mut rng, &escolha[atual], &visitado, None);
+        caminho.push(proximo);
+        visitado[proximo] = true;
+    }
+
+    Solucao::new(grafo, caminho)
+}
+
+// Tour de uma formiga do ACS: cada aresta percorrida, inclusive a que fecha o
+// ciclo, tem o feromônio puxado para τ0 na hora (atualização local), o que a
+// torna menos atraente para as próximas formigas da iteração
+fn formiga_acs<G: Instancia>(grafo: &G,
+                             feromonio: &mut Feromonio,
+                             escolha: &mut [Vec<f64>],
+                             visibilidade: &[Vec<f64>],
+                             alfa: f64,
+                             q0: f64,
+                             xi: f64,
+                             tau0: f64,
+                             simetrica: bool)
+                             -> Solucao<G::Peso> {
+    let mut rng = rand::thread_rng();
+    let n = grafo.num_vertices();
+    let mut visitado = vec![false; n];
+    let mut caminho = Vec::with_capacity(n);
+    let inicial = rng.gen_range(0, n);
+    caminho.push(inicial);
+    visitado[inicial] = true;
+
+    for k in 1..n + 1 {
+        let atual = caminho[k - 1];
+        let proximo = if k < n {
+            escolhe_proximo(&mut rng, &escolha[atual], &visitado, Some(q0))
+        } else {
+            inicial
+        };
+        let arestas = [(atual, proximo), (proximo, atual)];
+        for &(i, j) in &arestas[..if simetrica { 2 } else { 1 }] {
+            feromonio[i][j] = (1.0 - xi) * feromonio[i][j] + xi * tau0;
+            escolha[i][j] = feromonio[i][j].powf(alfa) * visibilidade[i][j];
+        }
+        if k < n {
+            caminho.push(proximo);
+            visitado[proximo] = true;
+        }
+    }
+
+    Solucao::new(grafo, caminho)
+}
+
+fn deposita(feromonio: &mut Feromonio, caminho: &Caminho, quantidade: f64, simetrica: bool) {
+    let n = caminho.len();
+    for t in 0..n {
+        let (i, j) = (caminho[t], caminho[(t + 1) % n]);
+        feromonio[i][j] += quantidade;
+        if simetrica {
+            feromonio[j][i] += quantidade;
+        }
+    }
+}
+
+// Limites do MAX-MIN para o melhor custo conhecido
+fn limites_mmas(n: usize, rho: f64, melhor: f64) -> (f64, f64) {
+    let maximo = 1.0 / (rho * melhor);
+    let p = P_MELHOR_MMAS.powf(1.0 / n as f64);
+    let escolhas_medias = (n as f64 / 2.0 - 1.0).max(1.0);
+    (maximo * (1.0 - p) / (escolhas_medias * p), maximo)
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia + Sync>(grafo: &G,
+                                  timeout: Duration,
+                                  max_iter: u64,
+                                  variante: Variante,
+                                  num_formigas: usize,
+                                  alfa: f64,
+                                  beta: f64,
+                                  rho: f64,
+                                  q0: f64,
+                                  xi: f64)
+                                  -> Result<(Solucao<G::Peso>, u64, Feromonio), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let t = Instant::now();
+    let n = grafo.num_vertices();
+    let num_formigas = num_formigas.max(1);
+    let simetrica = diagnostico::simetrica(grafo);
+
+    // η^β: o inverso da distância; arestas INF não são visíveis
+    let visibilidade = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j || !grafo.aresta(i, j) {
+                    0.0
+                } else {
+                    let d = grafo.distancia(i, j).to_f64().max(DISTANCIA_MINIMA);
+                    (1.0 / d).powf(beta)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let vizinho = Solucao::new(grafo, construcao::vizinho_mais_proximo(grafo, 0));
+    let c_nn = vizinho.fo().to_f64();
+    let (mut tau_min, mut tau_max) = limites_mmas(n, rho, c_nn);
+    let tau0 = match variante {
+        Variante::AntSystem => num_formigas as f64 / c_nn,
+        Variante::AntColonySystem => 1.0 / (n as f64 * c_nn),
+        Variante::MaxMin => tau_max,
+    };
+    let mut feromonio = vec![vec![tau0; n]; n];
+    // O vizinho mais próximo só calibra o feromônio inicial; o melhor tour vem
+    // das formigas
+    let mut best: Option<Solucao<G::Peso>> = None;
+
+    let mut it = 0;
+    let mut it_alvo = 0;
+    while it - it_alvo < max_iter && t.elapsed() < timeout {
+        let mut escolha = feromonio.iter()
+            .zip(&visibilidade)
+            .map(|(tau, eta)| {
+                tau.iter().zip(eta).map(|(&tau, &eta)| tau.powf(alfa) * eta).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut formigas = Vec::with_capacity(num_formigas);
+        if variante == Variante::AntColonySystem {
+            for _ in 0..num_formigas {
+                let s = formiga_acs(grafo,
+                                    &mut feromonio,
+                                    &mut escolha,
+                                    &visibilidade,
+                                    alfa,
+                                    q0,
+                                    xi,
+                                    tau0,
+                                    simetrica);
+                formigas.push(s);
+            }
+        } else {
+            let escolha = &escolha;
+            (0..num_formigas)
+                .into_par_iter()
+                .map(|_| formiga(grafo, escolha))
+                .collect_into(&mut formigas);
+        }
+        formigas.sort_by(|a, b| a.fo().compara(b.fo()));
+
+        if best.as_ref().map_or(true, |best| formigas[0].fo().menor(best.fo())) {
+            best = Some(formigas[0].clone());
+            it_alvo = it;
+            if variante == Variante::MaxMin {
+                let limites = limites_mmas(n, rho, formigas[0].fo().to_f64());
+                tau_min = limites.0;
+                tau_max = limites.1;
+            }
+        }
+
+        let global = best.as_ref().unwrap();
+        match variante {
+            Variante::AntSystem => {
+                for linha in &mut feromonio {
+                    for tau in linha.iter_mut() {
+                        *tau *= 1.0 - rho;
+                    }
+                }
+                for s in &formigas {
+                    deposita(&mut feromonio, s.caminho(), 1.0 / s.fo().to_f64(), simetrica);
+                }
+            }
+            Variante::AntColonySystem => {
+                let caminho = global.caminho();
+                let deposito = 1.0 / global.fo().to_f64();
+                for k in 0..n {
+                    let (i, j) = (caminho[k], caminho[(k + 1) % n]);
+                    feromonio[i][j] = (1.0 - rho) * feromonio[i][j] + rho * deposito;
+                    if simetrica {
+                        feromonio[j][i] = feromonio[i][j];
+                    }
+                }
+            }
+            Variante::MaxMin => {
+                for linha in &mut feromonio {
+                    for tau in linha.iter_mut() {
+                        *tau *= 1.0 - rho;
+                    }
+                }
+                let melhor = if it % INTERVALO_MELHOR_GLOBAL == 0 { global } else { &formigas[0] };
+                deposita(&mut feromonio, melhor.caminho(), 1.0 / melhor.fo().to_f64(), simetrica);
+                for linha in &mut feromonio {
+                    for tau in linha.iter_mut() {
+                        *tau = tau.max(tau_min).min(tau_max);
+                    }
+                }
+            }
+        }
+
+        it += 1;
+    }
+
+    Ok((best.unwrap_or(vizinho), it_alvo, feromonio))
+}
+
+pub fn escreve_feromonio<W: Write>(escritor: &mut W, feromonio: &Feromonio) -> io::Result<()> {
+    for linha in feromonio {
+        let valores = linha.iter().map(|tau| format!("{:e}", tau)).collect::<Vec<_>>();
+        writeln!(escritor, "{}", valores.join(" "))?;
+    }
+    Ok(())
+}
+
+// Matriz de feromônio em texto, uma linha por vértice
+#[allow(dead_code)]
+pub fn salva_feromonio(file: &str, feromonio: &Feromonio) -> io::Result<()> {
+    let mut escritor = BufWriter::new(File::create(Path::new(file))?);
+    escreve_feromonio(&mut escritor, feromonio)
+}
+
+// Colônia de formigas: Ant System, Ant Colony System ou MAX-MIN Ant System.
+// No AS e no MAX-MIN, as formigas de cada iteração são construídas em paralelo.
+pub struct Aco<'a, G: Instancia + Sync + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    max_iter: u64,
+    variante: Variante,
+    num_formigas: Option<usize>,
+    alfa: f64,
+    beta: f64,
+    rho: Option<f64>,
+    q0: f64,
+    xi: f64,
+}
+
+impl<'a, G: Instancia + Sync> Aco<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Aco<G> {
+        Aco {
+            grafo: grafo,
+            timeout: u64::MAX,
+            max_iter: 100,
+            variante: Variante::MaxMin,
+            num_formigas: None,
+            alfa: 1.0,
+            beta: 3.0,
+            rho: None,
+            q0: 0.9,
+            xi: 0.1,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Aco<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Iterações sem melhorar a melhor solução antes de parar
+    #[allow(dead_code)]
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Aco<'a, G> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn variante(&mut self, variante: Variante) -> &mut Aco<'a, G> {
+        self.variante = variante;
+        self
+    }
+
+    // Formigas por iteração; o padrão é uma por vértice, até 100
+    #[allow(dead_code)]
+    pub fn num_formigas(&mut self, num_formigas: usize) -> &mut Aco<'a, G> {
+        self.num_formigas = Some(num_formigas);
+        self
+    }
+
+    // Peso do feromônio na escolha das formigas
+    #[allow(dead_code)]
+    pub fn alfa(&mut self, alfa: f64) -> &mut Aco<'a, G> {
+        self.alfa = alfa;
+        self
+    }
+
+    // Peso da visibilidade (inverso da distância) na escolha das formigas
+    #[allow(dead_code)]
+    pub fn beta(&mut self, beta: f64) -> &mut Aco<'a, G> {
+        self.beta = beta;
+        self
+    }
+
+    // Taxa de evaporação; o padrão depende da variante: 0,5 no AS, 0,1 no ACS
+    // e 0,02 no MAX-MIN
+    #[allow(dead_code)]
+    pub fn rho(&mut self, rho: f64) -> &mut Aco<'a, G> {
+        self.rho = Some(rho);
+        self
+    }
+
+    // Probabilidade da escolha gulosa no ACS
+    #[allow(dead_code)]
+    pub fn q0(&mut self, q0: f64) -> &mut Aco<'a, G> {
+        self.q0 = q0;
+        self
+    }
+
+    // Taxa da atualização local do ACS
+    #[allow(dead_code)]
+    pub fn xi(&mut self, xi: f64) -> &mut Aco<'a, G> {
+        self.xi = xi;
+        self
+    }
+
+    // Como `solve`, mas devolve também a matriz de feromônio final
+    #[allow(dead_code)]
+    pub fn solve_com_feromonio(&self)
+                               -> Result<(Solucao<G::Peso>, u64, Feromonio), ErroConstrucao> {
+        let rho = self.rho.unwrap_or(match self.variante {
+            Variante::AntSystem => 0.5,
+            Variante::AntColonySystem => 0.1,
+            Variante::MaxMin => 0.02,
+        });
+        let num_formigas = self.num_formigas.unwrap_or(self.grafo.num_vertices().min(100));
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.max_iter,
+              self.variante,
+              num_formigas,
+              self.alfa,
+              self.beta,
+              rho,
+              self.q0,
+              self.xi)
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+        self.solve_com_feromonio().map(|(solucao, it, _)| (solucao, it))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_qualidade};
+
+    #[test]
+    fn tres_variantes() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let variantes = [Variante::AntSystem, Variante::AntColonySystem, Variante::MaxMin];
+        for &variante in &variantes {
+            let (s, _) = Aco::new(&g)
+                .max_iter(100)
+                .num_formigas(24)
+                .variante(variante)
+                .solve()
+                .unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 20);
+        }
+    }
+
+    #[test]
+    fn atualizacao_local_durante_a_construcao() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let n = 17;
+        let visibilidade = vec![vec![1.0; n]; n];
+        let mut feromonio = vec![vec![1.0; n]; n];
+        let mut escolha = feromonio.clone();
+        let s = formiga_acs(&g,
+                            &mut feromonio,
+                            &mut escolha,
+                            &visibilidade,
+                            1.0,
+                            0.9,
+                            0.1,
+                            0.5,
+                            true);
+        // Cada aresta do tour vai para 0,9 × 1 + 0,1 × 0,5, nos dois sentidos
+        let caminho = s.caminho();
+        for k in 0..n {
+            let (i, j) = (caminho[k], caminho[(k + 1) % n]);
+            assert!((feromonio[i][j] - 0.95).abs() < 1e-12);
+            assert_eq!(feromonio[j][i], feromonio[i][j]);
+            assert_eq!(escolha[i][j], feromonio[i][j]);
+        }
+        let alteradas = feromonio.iter().flat_map(|l| l.iter()).filter(|&&tau| tau < 1.0).count();
+        assert_eq!(alteradas, 2 * n);
+    }
+
+    #[test]
+    fn limites_do_max_min() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let (s, _, feromonio) =
+            Aco::new(&g).max_iter(20).variante(Variante::MaxMin).solve_com_feromonio().unwrap();
+        let (minimo, maximo) = limites_mmas(17, 0.02, s.fo() as f64);
+        for linha in &feromonio {
+            for &tau in linha {
+                assert!(tau >= minimo * 0.999 && tau <= maximo * 1.001);
+            }
+        }
+    }
+
+    #[test]
+    fn exporta_feromonio() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let (_, _, feromonio) = Aco::new(&g).max_iter(5).solve_com_feromonio().unwrap();
+        let mut saida = vec![];
+        escreve_feromonio(&mut saida, &feromonio).unwrap();
+        let texto = String::from_utf8(saida).unwrap();
+        let lido = texto.lines()
+            .map(|l| l.split(' ').map(|x| x.parse::<f64>().unwrap()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(lido, feromonio);
+    }
+}
@@ -6,53 +6,126 @@ use std::time::{Duration, Instant};
 use std::cmp::{min, max};
 use self::rand::{Rng, sample};
 use self::rayon::prelude::*;
-use grafo::{Solucao, Grafo, Caminho, Vertice};
-use grafo;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use construcao;
+use diagnostico;
+use busca_local::{Vnd, Vizinhanca};
+use candidatos::{self, Candidatos, TipoCandidatos};
+use eax::{self, Estrategia};
+use erro::ErroConstrucao;
 
-type Populacao = Vec<Solucao>;
+type Populacao<P> = Vec<Solucao<P>>;
 
+// Vizinhos por vértice nas listas de candidatos da busca local dos filhos
+const CANDIDATOS_MEMETICO: usize = 10;
+
+// Vizinhos por vértice considerados ao juntar os subtours do EAX
+const CANDIDATOS_EAX: usize = 10;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cruzamento {
+    Pmx,
+    Ox,
+    // Edge Assembly Crossover; nas instâncias assimétricas usa o PMX
+    Eax(Estrategia),
+}
+
+// Como o ótimo local encontrado pela busca local volta para a população
 #[allow(dead_code)]
-pub fn solve(grafo: &Grafo,
-             timeout: Duration, // 30s
-             max_iter: u64, // INF
-             pop_tam: usize, // 250, 500, 1000
-             xo_chance: f64, // 0.95, 0.99
-             // metodo de cruzamento: OX, PMX
-             // método de mutação: Swap, 2-opt
-             // método de seleção: Torneio-2, Torneio-4, Roleta
-             mut_chance: f64 /* 0.05 0.10 0.20 */)
-             -> (Solucao, u64) {
-    let mut pop = populacao_inicial(grafo, pop_tam);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Memetico {
+    // O filho é substituído pelo ótimo local
+    Lamarckiano,
+    // O filho mantém os genes e recebe só a fo do ótimo local
+    Baldwiniano,
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia + Sync>(grafo: &G,
+                                  timeout: Duration, // 30s
+                                  max_iter: u64, // INF
+                                  pop_tam: usize, // 250, 500, 1000
+                                  xo_chance: f64, // 0.95, 0.99
+                                  // metodo de cruzamento: OX, PMX
+                                  // método de mutação: Swap, 2-opt
+                                  // método de seleção: Torneio-2, Torneio-4, Roleta
+                                  mut_chance: f64, /* 0.05 0.10 0.20 */
+                                  cruzamento: Cruzamento,
+                                  sementes: &[Caminho],
+                                  pos_otimizacao: &[Vizinhanca],
+                                  memetico: Option<(Memetico, f64)>,
+                                  busca_local: &[Vizinhanca])
+                                  -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let mut pop = populacao_inicial(grafo, pop_tam, sementes)?;
     let mut best_fo = pop[0].fo();
+    let vnd = memetico.map(|_| {
+        Vnd::new(grafo,
+                 busca_local,
+                 Some((TipoCandidatos::MaisProximos, CANDIDATOS_MEMETICO)))
+    });
+    // No modo baldwiniano a fo dos indivíduos é a do ótimo local, que não
+    // está na população
+    let mut melhor_otimo: Option<Solucao<G::Peso>> = None;
+    let candidatos = match cruzamento {
+        Cruzamento::Eax(_) if diagnostico::simetrica(grafo) && grafo.num_vertices() >= 5 => {
+            Some(candidatos::mais_proximos(grafo, CANDIDATOS_EAX))
+        }
+        _ => None,
+    };
     let mut it = 0;
     let mut it_melhor = 0;
     let xo_num = (xo_chance * pop_tam as f64).ceil() as usize;
     let t = Instant::now();
 
     while it - it_melhor < max_iter && t.elapsed() < timeout {
-        let filhos;
+        let mut filhos;
         {
             let pais = selecao(&pop, xo_num);
-            filhos = recombinacao(grafo, pais, mut_chance);
+            filhos = recombinacao(grafo, pais, mut_chance, cruzamento, candidatos.as_ref());
+        }
+        if let (Some(vnd), Some((modo, fracao))) = (vnd.as_ref(), memetico) {
+            let (aprendidos, otimo) = aprendizado(grafo, &filhos, vnd, modo, fracao);
+            filhos = aprendidos;
+            if let Some(otimo) = otimo {
+                if melhor_otimo.as_ref().map_or(true, |m| otimo.fo().menor(m.fo())) {
+                    melhor_otimo = Some(otimo);
+                }
+            }
         }
         pop = proxima_geracao(pop, filhos, pop_tam);
 
-        if pop[0].fo() < best_fo {
+        if pop[0].fo().menor(best_fo) {
             it_melhor = it;
             best_fo = pop[0].fo();
         }
         it += 1;
     }
 
-    (pop.swap_remove(0), it_melhor)
+    let mut best = pop.swap_remove(0);
+    if let Some(otimo) = melhor_otimo {
+        // Um indivíduo baldwiniano vale pela fo aprendida; o tour é o ótimo
+        best = Solucao::new(grafo, best.caminho().clone());
+        if otimo.fo().menor(best.fo()) {
+            best = otimo;
+        }
+    }
+    if pos_otimizacao.is_empty() {
+        return Ok((best, it_melhor));
+    }
+    let vnd = Vnd::new(grafo, pos_otimizacao, None);
+    Ok((vnd.aplica(grafo, &best), it_melhor))
 }
 
 #[allow(dead_code)]
-fn gen_roleta(pop: &Populacao) -> Vec<f32> {
-    let total = pop.iter().map(|s| 1.0 / s.fo() as f32).sum::<f32>();
+fn gen_roleta<P: Custo>(pop: &Populacao<P>) -> Vec<f32> {
+    let total = pop.iter().map(|s| 1.0 / s.fo().to_f64() as f32).sum::<f32>();
     pop.iter()
         .scan(0.0, |state, prob| {
-            *state += prob.fo() as f32 / total;
+            *state += 1.0 / prob.fo().to_f64() as f32 / total;
             Some(*state)
         })
         .collect()
@@ -70,14 +143,14 @@ fn get_index_from_roleta(roleta: &[f32]) -> usize {
 }
 
 #[allow(dead_code)]
-fn seleciona_pais<'a>(pop: &'a Populacao, roleta: &[f32]) -> (&'a Caminho, &'a Caminho) {
+fn seleciona_pais<'a, P: Custo>(pop: &'a Populacao<P>, roleta: &[f32]) -> (&'a Caminho, &'a Caminho) {
     let pai1 = pop[get_index_from_roleta(roleta)].caminho();
     let pai2 = pop[get_index_from_roleta(roleta)].caminho();
     (pai1, pai2)
 }
 
 #[allow(dead_code)]
-fn selecao(pop: &Populacao, xo_num: usize) -> Vec<(&Caminho, &Caminho)> {
+fn selecao<P: Custo>(pop: &Populacao<P>, xo_num: usize) -> Vec<(&Caminho, &Caminho)> {
     let roleta = gen_roleta(pop);
     let mut pais = Vec::with_capacity(xo_num);
     (0..xo_num)
@@ -88,32 +161,42 @@ fn selecao(pop: &Populacao, xo_num: usize) -> Vec<(&Caminho, &Caminho)> {
 }
 
 #[allow(dead_code)]
-fn proxima_geracao(atual: Populacao, filhos: Populacao, pop_tam: usize) -> Populacao {
+fn proxima_geracao<P>(atual: Populacao<P>, filhos: Populacao<P>, pop_tam: usize) -> Populacao<P>
+    where P: Custo
+{
     let mut proxima = atual;
     proxima.extend(filhos.into_iter());
-    proxima.sort_by_key(Solucao::fo);
+    proxima.sort_by(|a, b| a.fo().compara(b.fo()));
     proxima.truncate(pop_tam);
     proxima
 }
 
 #[allow(dead_code)]
-fn populacao_inicial(grafo: &Grafo, pop_tam: usize) -> Populacao {
-    let mut pop = (0..pop_tam).map(|_| individuo_aleatorio(grafo)).collect::<Vec<_>>();
-    pop.sort_by_key(Solucao::fo);
-    pop
+fn populacao_inicial<G>(grafo: &G,
+                        pop_tam: usize,
+                        sementes: &[Caminho])
+                        -> Result<Populacao<G::Peso>, ErroConstrucao>
+    where G: Instancia
+{
+    let mut pop = sementes.iter()
+        .take(pop_tam)
+        .map(|c| Ok(Solucao::new(grafo, c.clone())))
+        .chain((sementes.len()..pop_tam).map(|_| individuo_aleatorio(grafo)))
+        .collect::<Result<Vec<_>, _>>()?;
+    pop.sort_by(|a, b| a.fo().compara(b.fo()));
+    Ok(pop)
 }
 
 #[allow(dead_code)]
-fn individuo_aleatorio(grafo: &Grafo) -> Solucao {
-    loop {
-        if let Some(caminho) = caminho_aleatorio(grafo) {
-            return Solucao::new(grafo, caminho);
-        }
-    }
+fn individuo_aleatorio<G: Instancia>(grafo: &G) -> Result<Solucao<G::Peso>, ErroConstrucao> {
+    let caminho = construcao::com_retentativas(&mut rand::thread_rng(),
+                                               grafo,
+                                               |_| caminho_aleatorio(grafo))?;
+    Ok(Solucao::new(grafo, caminho))
 }
 
 #[allow(dead_code)]
-fn caminho_aleatorio(grafo: &Grafo) -> Option<Caminho> {
+fn caminho_aleatorio<G: Instancia>(grafo: &G) -> Option<Caminho> {
     let mut rng = rand::thread_rng();
     let num_vertices = grafo.num_vertices();
     let mut caminho = Vec::with_capacity(num_vertices);
@@ -129,7 +212,7 @@ fn caminho_aleatorio(grafo: &Grafo) -> Option<Caminho> {
         let atual = caminho[caminho.len() - 1];
         let abertos = grafo.adjacentes(atual)
             .zip(marcados.iter())
-            .filter(|&((_, &peso), marc)| !marc && peso != grafo::INF)
+            .filter(|&((_, peso), marc)| !marc && !peso.is_inf())
             .map(|((vert, _), _)| vert)
             .collect::<Vec<_>>();
 
@@ -143,6 +226,10 @@ fn caminho_aleatorio(grafo: &Grafo) -> Option<Caminho> {
         num_marcados += 1;
     }
 
+    if !grafo.aresta(caminho[num_vertices - 1], inicial) {
+        return None;
+    }
+
     Some(caminho)
 }
 
@@ -216,19 +303,78 @@ fn ordered_crossover(pai1: &Caminho, pai2: &Caminho) -> Caminho {
     filho.into_iter().map(|o| o.expect("Erro no OX")).collect()
 }
 
+// `candidatos` só existe quando o EAX pode ser usado
+fn cruza<G: Instancia>(grafo: &G,
+                       pai1: &Caminho,
+                       pai2: &Caminho,
+                       cruzamento: Cruzamento,
+                       candidatos: Option<&Candidatos>)
+                       -> Caminho {
+    match (cruzamento, candidatos) {
+        (Cruzamento::Eax(estrategia), Some(candidatos)) => {
+            eax::eax(&mut rand::thread_rng(), grafo, pai1, pai2, estrategia, candidatos)
+        }
+        (Cruzamento::Ox, _) => ordered_crossover(pai1, pai2),
+        _ => pmx_crossover(pai1, pai2),
+    }
+}
+
 #[allow(dead_code)]
-fn recombinacao(grafo: &Grafo, pais: Vec<(&Caminho, &Caminho)>, mut_chance: f64) -> Populacao {
+fn recombinacao<G: Instancia + Sync>(grafo: &G,
+                                     pais: Vec<(&Caminho, &Caminho)>,
+                                     mut_chance: f64,
+                                     cruzamento: Cruzamento,
+                                     candidatos: Option<&Candidatos>)
+                                     -> Populacao<G::Peso> {
     let mut filhos = Vec::with_capacity(pais.len() * 2);
     pais.par_iter()
-        .map(|&(pai1, pai2)| pmx_crossover(pai1, pai2))
+        .map(|&(pai1, pai2)| cruza(grafo, pai1, pai2, cruzamento, candidatos))
         .chain(pais.par_iter()
-            .map(|&(pai2, pai1)| pmx_crossover(pai2, pai1)))
+            .map(|&(pai2, pai1)| cruza(grafo, pai2, pai1, cruzamento, candidatos)))
         .map(|c| mutacao(c, mut_chance))
         .map(|c| Solucao::new(grafo, c))
         .collect_into(&mut filhos);
     filhos
 }
 
+// Busca local numa fração sorteada dos filhos, em paralelo. Devolve os filhos
+// e, no modo baldwiniano, o melhor ótimo local encontrado.
+fn aprendizado<G: Instancia + Sync>(grafo: &G,
+                                    filhos: &Populacao<G::Peso>,
+                                    vnd: &Vnd,
+                                    modo: Memetico,
+                                    fracao: f64)
+                                    -> (Populacao<G::Peso>, Option<Solucao<G::Peso>>) {
+    let mut resultado = Vec::with_capacity(filhos.len());
+    filhos.par_iter()
+        .map(|filho| {
+            if rand::thread_rng().gen::<f64>() >= fracao {
+                return (filho.clone(), None);
+            }
+            let otimo = vnd.aplica(grafo, filho);
+            match modo {
+                Memetico::Lamarckiano => (otimo, None),
+                Memetico::Baldwiniano => {
+                    (Solucao::com_fo(filho.caminho().clone(), otimo.fo()), Some(otimo))
+                }
+            }
+        })
+        .collect_into(&mut resultado);
+
+    let mut melhor: Option<Solucao<G::Peso>> = None;
+    let filhos = resultado.into_iter()
+        .map(|(filho, otimo)| {
+            if let Some(otimo) = otimo {
+                if melhor.as_ref().map_or(true, |m| otimo.fo().menor(m.fo())) {
+                    melhor = Some(otimo);
+                }
+            }
+            filho
+        })
+        .collect();
+    (filhos, melhor)
+}
+
 #[allow(dead_code)]
 fn swap_vertices(mut caminho: Caminho) -> Caminho {
     let (i, j) = gen_points(caminho.len());
@@ -246,18 +392,23 @@ fn mutacao(caminho: Caminho, mut_chance: f64) -> Caminho {
     }
 }
 
-pub struct Ag<'a> {
-    grafo: &'a Grafo,
+pub struct Ag<'a, G: Instancia + Sync + 'a> {
+    grafo: &'a G,
     timeout: u64,
     max_iter: u64,
     pop_tam: usize,
     xo_chance: f64,
     mut_chance: f64,
+    cruzamento: Cruzamento,
+    sementes: Vec<Caminho>,
+    pos_otimizacao: Vec<Vizinhanca>,
+    memetico: Option<(Memetico, f64)>,
+    busca_local: Vec<Vizinhanca>,
 }
 
-impl<'a> Ag<'a> {
+impl<'a, G: Instancia + Sync> Ag<'a, G> {
     #[allow(dead_code)]
-    pub fn new(grafo: &Grafo) -> Ag {
+    pub fn new(grafo: &G) -> Ag<G> {
         Ag {
             grafo: grafo,
             timeout: u64::MAX,
@@ -265,46 +416,191 @@ impl<'a> Ag<'a> {
             pop_tam: 200,
             xo_chance: 0.8,
             mut_chance: 0.1,
+            cruzamento: Cruzamento::Pmx,
+            sementes: vec![],
+            pos_otimizacao: vec![],
+            memetico: None,
+            busca_local: vec![Vizinhanca::TwoOpt, Vizinhanca::OrOpt],
         }
     }
 
     #[allow(dead_code)]
-    pub fn solve(&self) -> (Solucao, u64) {
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
         solve(self.grafo,
               Duration::from_secs(self.timeout),
               self.max_iter,
               self.pop_tam,
               self.xo_chance,
-              self.mut_chance)
+              self.mut_chance,
+              self.cruzamento,
+              &self.sementes,
+              &self.pos_otimizacao,
+              self.memetico,
+              &self.busca_local)
     }
 
     #[allow(dead_code)]
-    pub fn timeout(&mut self, timeout: u64) -> &mut Ag<'a> {
+    pub fn timeout(&mut self, timeout: u64) -> &mut Ag<'a, G> {
         self.timeout = timeout;
         self
     }
 
     #[allow(dead_code)]
-    pub fn max_iter(&mut self, max_iter: u64) -> &mut Ag<'a> {
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Ag<'a, G> {
         self.max_iter = max_iter;
         self
     }
 
     #[allow(dead_code)]
-    pub fn pop_tam(&mut self, pop_tam: usize) -> &mut Ag<'a> {
+    pub fn pop_tam(&mut self, pop_tam: usize) -> &mut Ag<'a, G> {
         self.pop_tam = pop_tam;
         self
     }
 
     #[allow(dead_code)]
-    pub fn xo_chance(&mut self, xo_chance: f64) -> &mut Ag<'a> {
+    pub fn xo_chance(&mut self, xo_chance: f64) -> &mut Ag<'a, G> {
         self.xo_chance = xo_chance;
         self
     }
 
     #[allow(dead_code)]
-    pub fn mut_chance(&mut self, mut_chance: f64) -> &mut Ag<'a> {
+    pub fn mut_chance(&mut self, mut_chance: f64) -> &mut Ag<'a, G> {
         self.mut_chance = mut_chance;
         self
     }
+
+    #[allow(dead_code)]
+    pub fn cruzamento(&mut self, cruzamento: Cruzamento) -> &mut Ag<'a, G> {
+        self.cruzamento = cruzamento;
+        self
+    }
+
+    // Indivíduo incluído na população inicial, por exemplo de uma heurística
+    // construtiva
+    #[allow(dead_code)]
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Ag<'a, G> {
+        self.sementes.push(caminho);
+        self
+    }
+
+    // Busca local aplicada ao melhor indivíduo ao fim da evolução, por exemplo
+    // `vec![Vizinhanca::LinKernighan]`. Vazia por padrão.
+    #[allow(dead_code)]
+    pub fn pos_otimizacao(&mut self, vizinhancas: Vec<Vizinhanca>) -> &mut Ag<'a, G> {
+        self.pos_otimizacao = vizinhancas;
+        self
+    }
+
+    // Modo memético: busca local em cada filho com probabilidade `fracao`
+    // (1.0 para todos)
+    #[allow(dead_code)]
+    pub fn memetico(&mut self, modo: Memetico, fracao: f64) -> &mut Ag<'a, G> {
+        self.memetico = Some((modo, fracao));
+        self
+    }
+
+    // Vizinhanças da busca local do modo memético; 2-opt e Or-opt por padrão
+    #[allow(dead_code)]
+    pub fn busca_local(&mut self, vizinhancas: Vec<Vizinhanca>) -> &mut Ag<'a, G> {
+        self.busca_local = vizinhancas;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_fo, confere_qualidade};
+
+    #[test]
+    fn memetico_lamarckiano_e_baldwiniano() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        for &modo in &[Memetico::Lamarckiano, Memetico::Baldwiniano] {
+            let (s, _) = Ag::new(&g)
+                .max_iter(10)
+                .pop_tam(30)
+                .memetico(modo, 0.5)
+                .solve()
+                .unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 5);
+        }
+    }
+
+    #[test]
+    fn cruzamentos() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let opcoes = [Cruzamento::Pmx,
+                      Cruzamento::Ox,
+                      Cruzamento::Eax(Estrategia::Unica),
+                      Cruzamento::Eax(Estrategia::Bloco)];
+        for &cruzamento in &opcoes {
+            let (s, _) = Ag::new(&g)
+                .max_iter(5)
+                .pop_tam(30)
+                .cruzamento(cruzamento)
+                .solve()
+                .unwrap();
+            confere_fo(&g, &s);
+        }
+
+        // Com a roleta favorecendo os melhores indivíduos, o EAX recombina
+        // pais distintos e chega perto do ótimo sem mutação
+        let (s, _) = Ag::new(&g)
+            .max_iter(20)
+            .pop_tam(100)
+            .mut_chance(0.0)
+            .cruzamento(Cruzamento::Eax(Estrategia::Unica))
+            .solve()
+            .unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 2);
+    }
+
+    #[test]
+    fn aprendizado_dos_filhos() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let filhos = (0..10).map(|_| individuo_aleatorio(&g).unwrap()).collect::<Vec<_>>();
+        let vnd = Vnd::new(&g, &[Vizinhanca::TwoOpt], None);
+
+        let (aprendidos, otimo) = aprendizado(&g, &filhos, &vnd, Memetico::Lamarckiano, 1.0);
+        assert!(otimo.is_none());
+        for (filho, aprendido) in filhos.iter().zip(&aprendidos) {
+            assert!(aprendido.fo() <= filho.fo());
+            confere_fo(&g, &aprendido);
+        }
+
+        let (aprendidos, otimo) = aprendizado(&g, &filhos, &vnd, Memetico::Baldwiniano, 1.0);
+        let otimo = otimo.unwrap();
+        for (filho, aprendido) in filhos.iter().zip(&aprendidos) {
+            assert_eq!(aprendido.caminho(), filho.caminho());
+            assert!(otimo.fo() <= aprendido.fo());
+        }
+        confere_fo(&g, &otimo);
+
+        // Fração zero: nenhum filho passa pela busca local
+        let (aprendidos, _) = aprendizado(&g, &filhos, &vnd, Memetico::Lamarckiano, 0.0);
+        for (filho, aprendido) in filhos.iter().zip(&aprendidos) {
+            assert_eq!(aprendido.caminho(), filho.caminho());
+        }
+    }
+
+    #[test]
+    fn roleta_favorece_menor_fo() {
+        let pop = [10, 20, 40]
+            .iter()
+            .map(|&fo| Solucao::com_fo(vec![0, 1, 2, 3], fo))
+            .collect::<Populacao<u64>>();
+        let roleta = gen_roleta(&pop);
+        // Fatias proporcionais a 1/fo: 4/7, 2/7 e 1/7
+        let fatias = [roleta[0], roleta[1] - roleta[0], roleta[2] - roleta[1]];
+        for (fatia, esperada) in fatias.iter().zip(&[4.0f32 / 7.0, 2.0 / 7.0, 1.0 / 7.0]) {
+            assert!((fatia - esperada).abs() < 1e-5, "{:?}", roleta);
+        }
+
+        let mut sorteios = [0; 3];
+        for _ in 0..7000 {
+            sorteios[get_index_from_roleta(&roleta)] += 1;
+        }
+        assert!(sorteios[0] > sorteios[1] && sorteios[1] > sorteios[2], "{:?}", sorteios);
+    }
 }
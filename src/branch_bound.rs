@@ -0,0 +1,386 @@
+use std::u64;
+use std::time::{Duration, Instant};
+use grafo::{Solucao, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use grasp::Grasp;
+use limite_inferior::{self, UmaArvore};
+use erro::ErroConstrucao;
+
+// Custo usado para arestas excluídas e para forçar a escolha das incluídas
+const PROIBIDA: f64 = 1e12;
+const FORCADA: f64 = 1e15;
+
+const ITER_NO: usize = 30;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Estado {
+    Livre,
+    Incluida,
+    Excluida,
+}
+
+// Resultado do branch-and-bound: o melhor tour e um limite inferior provado
+pub struct Resultado<P> {
+    pub solucao: Solucao<P>,
+    pub limite_inferior: P,
+    pub otimo: bool,
+    pub nos: u64,
+}
+
+impl<P: Custo> Resultado<P> {
+    // Distância relativa entre o tour e o limite inferior
+    #[allow(dead_code)]
+    pub fn gap(&self) -> f64 {
+        limite_inferior::gap(self.solucao.fo(), self.limite_inferior)
+    }
+}
+
+struct Busca<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    custos: Vec<Vec<f64>>,
+    estado: Vec<Vec<Estado>>,
+    graus_incluidos: Vec<usize>,
+    desfazer: Vec<(Vertice, Vertice, Estado)>,
+    melhor: Solucao<G::Peso>,
+    inicio: Instant,
+    timeout: Duration,
+    nos: u64,
+}
+
+impl<'a, G: Instancia> Busca<'a, G> {
+    fn esgotado(&self) -> bool {
+        self.inicio.elapsed() >= self.timeout
+    }
+
+    fn podavel(&self, limite: f64) -> bool {
+        !G::Peso::limite_inferior(limite).menor(self.melhor.fo())
+    }
+
+    fn custo(&self, i: Vertice, j: Vertice) -> f64 {
+        match self.estado[i][j] {
+            Estado::Excluida => PROIBIDA,
+            _ => self.custos[i][j],
+        }
+    }
+
+    // Chave de seleção: arestas incluídas vêm antes de todas as outras
+    fn chave(&self, i: Vertice, j: Vertice) -> f64 {
+        match self.estado[i][j] {
+            Estado::Incluida => self.custo(i, j) - FORCADA,
+            _ => self.custo(i, j),
+        }
+    }
+
+    fn subgradiente(&self, pi: &mut Vec<f64>, iteracoes: usize) -> UmaArvore {
+        let n = self.custos.len();
+        limite_inferior::subgradiente(pi,
+                                      iteracoes,
+                                      self.melhor.fo().to_f64(),
+                                      |pi| {
+                                          limite_inferior::uma_arvore(n,
+                                                                      pi,
+                                                                      |i, j| self.custo(i, j),
+                                                                      |i, j| self.chave(i, j))
+                                      },
+                                      |lb| self.podavel(lb))
+    }
+
+    fn muda(&mut self, i: Vertice, j: Vertice, estado: Estado) {
+        self.desfazer.push((i, j, self.estado[i][j]));
+        self.estado[i][j] = estado;
+        self.estado[j][i] = estado;
+        if estado == Estado::Incluida {
+            self.graus_incluidos[i] += 1;
+            self.graus_incluidos[j] += 1;
+        }
+    }
+
+    fn restaura(&mut self, marca: usize) {
+        while self.desfazer.len() > marca {
+            let (i, j, antigo) = self.desfazer.pop().unwrap();
+            if self.estado[i][j] == Estado::Incluida {
+                self.graus_incluidos[i] -= 1;
+                self.graus_incluidos[j] -= 1;
+            }
+            self.estado[i][j] = antigo;
+            self.estado[j][i] = antigo;
+        }
+    }
+
+    // Outra ponta, e número de vértices, do caminho de arestas incluídas que
+    // termina em `v`
+    fn extremo(&self, v: Vertice) -> (Vertice, usize) {
+        let n = self.custos.len();
+        let (mut anterior, mut atual, mut tamanho) = (v, v, 1);
+        loop {
+            let prox = (0..n).find(|&w| {
+                w != anterior && w != atual && self.estado[atual][w] == Estado::Incluida
+            });
+            match prox {
+                Some(w) if w != v => {
+                    anterior = atual;
+                    atual = w;
+                    tamanho += 1;
+                }
+                _ => return (atual, tamanho),
+            }
+        }
+    }
+
+    fn exclui(&mut self, i: Vertice, j: Vertice) -> bool {
+        match self.estado[i][j] {
+            Estado::Excluida => true,
+            Estado::Incluida => false,
+            Estado::Livre => {
+                self.muda(i, j, Estado::Excluida);
+                true
+            }
+        }
+    }
+
+    // Inclui a aresta e propaga: vértices com duas arestas incluídas perdem as
+    // demais e a aresta que fecharia um subciclo é excluída. Devolve false se o
+    // subproblema ficar infactível.
+    fn inclui(&mut self, i: Vertice, j: Vertice) -> bool {
+        match self.estado[i][j] {
+            Estado::Incluida => return true,
+            Estado::Excluida => return false,
+            Estado::Livre => {}
+        }
+        if self.graus_incluidos[i] == 2 || self.graus_incluidos[j] == 2 {
+            return false;
+        }
+
+        let n = self.custos.len();
+        let (a, tamanho_i) = self.extremo(i);
+        let (b, tamanho_j) = self.extremo(j);
+        if a == j && tamanho_i < n {
+            return false;
+        }
+        self.muda(i, j, Estado::Incluida);
+
+        for &v in &[i, j] {
+            if self.graus_incluidos[v] == 2 {
+                for w in 0..n {
+                    if w != v && self.estado[v][w] == Estado::Livre {
+                        self.muda(v, w, Estado::Excluida);
+                    }
+                }
+            }
+        }
+        if tamanho_i + tamanho_j < n && self.estado[a][b] == Estado::Livre {
+            self.muda(a, b, Estado::Excluida);
+        }
+        true
+    }
+
+    // Busca em profundidade. Devolve o menor limite dos nós que ficaram sem
+    // explorar por falta de tempo.
+    fn explora(&mut self, mut pi: Vec<f64>, iteracoes: usize) -> Option<f64> {
+        self.nos += 1;
+        let arvore = self.subgradiente(&mut pi, iteracoes);
+        let limite = arvore.custo;
+        if self.podavel(limite) {
+            return None;
+        }
+        if arvore.is_tour() {
+            let solucao = Solucao::new(self.grafo, arvore.tour());
+            if solucao.fo().menor(self.melhor.fo()) {
+                self.melhor = solucao;
+            }
+            return None;
+        }
+
+        // Ramifica no vértice de maior grau, pelas arestas livres da 1-árvore
+        let n = self.custos.len();
+        let v = (0..n).max_by_key(|&v| (arvore.graus[v], n - v)).unwrap();
+        let mut livres = arvore.arestas
+            .iter()
+            .filter(|&&(i, j)| (i == v || j == v) && self.estado[i][j] == Estado::Livre)
+            .map(|&(i, j)| if i == v { j } else { i })
+            .collect::<Vec<_>>();
+        livres.sort_by(|&a, &b| self.custos[v][a].partial_cmp(&self.custos[v][b]).unwrap());
+
+        // Filhos: exclui e1; inclui e1 e exclui e2; inclui e1 e e2
+        let mut filhos = vec![vec![(livres[0], false)], vec![(livres[0], true)]];
+        if self.graus_incluidos[v] == 0 && livres.len() > 1 {
+            filhos[1].push((livres[1], false));
+            filhos.push(vec![(livres[0], true), (livres[1], true)]);
+        }
+
+        let mut pendente: Option<f64> = None;
+        for filho in filhos {
+            if self.esgotado() {
+                pendente = Some(pendente.map_or(limite, |p| p.min(limite)));
+                break;
+            }
+            let marca = self.desfazer.len();
+            let factivel = filho.iter()
+                .all(|&(w, incluir)| if incluir { self.inclui(v, w) } else { self.exclui(v, w) });
+            if factivel {
+                if let Some(p) = self.explora(pi.clone(), ITER_NO) {
+                    pendente = Some(pendente.map_or(p, |q| q.min(p)));
+                }
+            }
+            self.restaura(marca);
+        }
+        pendente
+    }
+}
+
+fn simetrica<G: Instancia>(grafo: &G) -> bool {
+    let n = grafo.num_vertices();
+    grafo.coordenadas().is_some() ||
+    (0..n).all(|i| (i + 1..n).all(|j| grafo.distancia(i, j).igual(grafo.distancia(j, i))))
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           timeout: Duration,
+                           iter_raiz: usize,
+                           iter_grasp: u64)
+                           -> Result<Resultado<G::Peso>, ErroConstrucao> {
+    if !simetrica(grafo) {
+        return Err(ErroConstrucao::NaoSuportada("1-tree bounds need symmetric weights"
+            .to_string()));
+    }
+    let (inicial, _) = Grasp::new(grafo).max_iter(iter_grasp).num_vizinhos(2).solve()?;
+    let n = grafo.num_vertices();
+    if n <= 3 {
+        return Ok(Resultado {
+            limite_inferior: inicial.fo(),
+            solucao: inicial,
+            otimo: true,
+            nos: 0,
+        });
+    }
+
+    let mut busca = Busca {
+        grafo: grafo,
+        custos: (0..n)
+            .map(|i| (0..n).map(|j| grafo.distancia(i, j).to_f64()).collect())
+            .collect(),
+        estado: vec![vec![Estado::Livre; n]; n],
+        graus_incluidos: vec![0; n],
+        desfazer: vec![],
+        melhor: inicial,
+        inicio: Instant::now(),
+        timeout: timeout,
+        nos: 0,
+    };
+    for i in 0..n {
+        busca.estado[i][i] = Estado::Excluida;
+    }
+
+    let pendente = busca.explora(vec![0.0; n], iter_raiz);
+    let limite_inferior = match pendente {
+        Some(limite) if G::Peso::limite_inferior(limite).menor(busca.melhor.fo()) => {
+            G::Peso::limite_inferior(limite)
+        }
+        _ => busca.melhor.fo(),
+    };
+
+    Ok(Resultado {
+        otimo: !limite_inferior.menor(busca.melhor.fo()),
+        limite_inferior: limite_inferior,
+        solucao: busca.melhor,
+        nos: busca.nos,
+    })
+}
+
+// Solver exato para instâncias simétricas de tamanho médio
+pub struct BranchBound<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    iter_raiz: usize,
+    iter_grasp: u64,
+}
+
+impl<'a, G: Instancia> BranchBound<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> BranchBound<G> {
+        BranchBound {
+            grafo: grafo,
+            timeout: u64::MAX,
+            iter_raiz: 1000,
+            iter_grasp: 5,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut BranchBound<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Iterações do subgradiente na raiz; os demais nós usam poucas, partindo
+    // das penalidades do pai
+    #[allow(dead_code)]
+    pub fn iter_raiz(&mut self, iter_raiz: usize) -> &mut BranchBound<'a, G> {
+        self.iter_raiz = iter_raiz;
+        self
+    }
+
+    // Iterações sem melhora do GRASP que fornece o limite superior inicial
+    #[allow(dead_code)]
+    pub fn iter_grasp(&mut self, iter_grasp: u64) -> &mut BranchBound<'a, G> {
+        self.iter_grasp = iter_grasp;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<Resultado<G::Peso>, ErroConstrucao> {
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.iter_raiz,
+              self.iter_grasp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Peso};
+    use held_karp::HeldKarp;
+
+    fn otimo(arquivo: &str) -> Peso {
+        let g: Grafo = Grafo::from_tsplib(arquivo).unwrap();
+        let r = BranchBound::new(&g).solve().unwrap();
+        assert!(r.otimo);
+        assert_eq!(r.limite_inferior, r.solucao.fo());
+        r.solucao.fo()
+    }
+
+    #[test]
+    fn otimos_conhecidos() {
+        assert_eq!(otimo("instances/gr17.tsp"), 2085);
+        assert_eq!(otimo("instances/fri26.tsp"), 937);
+        assert_eq!(otimo("instances/dantzig42.tsp"), 699);
+        assert_eq!(otimo("instances/att48.tsp"), 10628);
+    }
+
+    #[test]
+    fn igual_held_karp() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let r = BranchBound::new(&g).solve().unwrap();
+        assert_eq!(r.solucao.fo(), HeldKarp::new(&g).solve().unwrap().fo());
+    }
+
+    #[test]
+    fn sem_tempo_informa_gap() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let r = BranchBound::new(&g).timeout(0).solve().unwrap();
+        assert!(r.limite_inferior <= 10628 && r.solucao.fo() >= 10628);
+        assert!(r.gap() >= 0.0);
+    }
+
+    #[test]
+    fn assimetrica() {
+        let g = Grafo::from_matriz(vec![vec![0, 1, 2], vec![2, 0, 1], vec![1, 2, 0]]);
+        match BranchBound::new(&g).solve() {
+            Err(ErroConstrucao::NaoSuportada(_)) => {}
+            _ => panic!("esperava instância não suportada"),
+        }
+    }
+}
@@ -0,0 +1,859 @@
+use std::collections::VecDeque;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use candidatos::{self, Candidatos, TipoCandidatos};
+use diagnostico;
+use vizinhanca::{Estrutura, DoisOpt, Troca, Insercao, OrOpt, TresOpt, LinKernighan};
+
+// Tamanho máximo dos trechos movidos pelo Or-opt
+const MAX_TRECHO_OR_OPT: usize = 3;
+
+// Vizinhos por vértice quando 3-opt e Lin-Kernighan rodam sem listas
+// configuradas
+const CANDIDATOS_PADRAO: usize = 10;
+
+// Vizinhanças disponíveis para as buscas locais dos solvers
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Vizinhanca {
+    TwoOpt,
+    OrOpt,
+    ThreeOpt,
+    // Nas instâncias assimétricas, cai para o or3opt
+    LinKernighan,
+    // Troca dois vértices de posição
+    Troca,
+    // Move um vértice para outra posição
+    Insercao,
+}
+
+// Tour em vetor, com a posição de cada vértice, para achar sucessores e
+// antecessores em O(1)
+pub struct Tour {
+    ordem: Vec<Vertice>,
+    posicao: Vec<usize>,
+}
+
+impl Tour {
+    pub fn new(caminho: &Caminho) -> Tour {
+        let mut posicao = vec![0; caminho.len()];
+        for (i, &v) in caminho.iter().enumerate() {
+            posicao[v] = i;
+        }
+        Tour {
+            ordem: caminho.clone(),
+            posicao: posicao,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.ordem.len()
+    }
+
+    pub fn sucessor(&self, v: Vertice) -> Vertice {
+        let i = self.posicao[v] + 1;
+        if i == self.ordem.len() { self.ordem[0] } else { self.ordem[i] }
+    }
+
+    pub fn antecessor(&self, v: Vertice) -> Vertice {
+        match self.posicao[v] {
+            0 => self.ordem[self.ordem.len() - 1],
+            i => self.ordem[i - 1],
+        }
+    }
+
+    // Inverte o trecho que vai de `a` até `b` no sentido do tour. Para
+    // instâncias simétricas, inverter o complemento dá o mesmo ciclo, então
+    // inverte o lado menor.
+    pub fn inverte(&mut self, a: Vertice, b: Vertice) {
+        let n = self.ordem.len();
+        let (mut i, mut j) = (self.posicao[a], self.posicao[b]);
+        let mut tamanho = (j + n - i) % n + 1;
+        if 2 * tamanho > n {
+            i = (j + 1) % n;
+            j = (self.posicao[a] + n - 1) % n;
+            tamanho = n - tamanho;
+        }
+        for _ in 0..tamanho / 2 {
+            self.ordem.swap(i, j);
+            self.posicao[self.ordem[i]] = i;
+            self.posicao[self.ordem[j]] = j;
+            i = (i + 1) % n;
+            j = (j + n - 1) % n;
+        }
+    }
+
+    pub fn caminho(&self) -> Caminho {
+        self.ordem.clone()
+    }
+}
+
+// Procura, a partir de `a`, um movimento 2-opt que melhore o tour, trocando
+// uma aresta de `a` por uma aresta até um de seus candidatos. Aplica o
+// primeiro encontrado e devolve os vértices afetados.
+fn melhora_2opt<G: Instancia>(grafo: &G,
+                              tour: &mut Tour,
+                              fo: &mut G::Peso,
+                              candidatos: &[Vertice],
+                              a: Vertice)
+                              -> Option<[Vertice; 4]> {
+    for &sucessor in &[true, false] {
+        let b = if sucessor { tour.sucessor(a) } else { tour.antecessor(a) };
+        let d_ab = grafo.distancia(a, b);
+        for &c in candidatos {
+            let d_ac = grafo.distancia(a, c);
+            // Lista ordenada: daqui em diante a nova aresta não é mais curta
+            if !d_ac.menor(d_ab) {
+                break;
+            }
+            let e = if sucessor { tour.sucessor(c) } else { tour.antecessor(c) };
+            if c == b || e == a {
+                continue;
+            }
+            let removidas = d_ab + grafo.distancia(c, e);
+            let adicionadas = d_ac + grafo.distancia(b, e);
+            if adicionadas.menor(removidas) {
+                // a b ... c e  =>  a c ... b e
+                if sucessor {
+                    tour.inverte(b, c);
+                } else {
+                    tour.inverte(c, b);
+                }
+                *fo = *fo + adicionadas - removidas;
+                return Some([a, b, c, e]);
+            }
+        }
+    }
+    None
+}
+
+// 2-opt first-improvement restrito às listas de candidatos, com don't-look
+// bits: só os vértices cujas arestas mudaram voltam a ser examinados. Supõe
+// pesos simétricos.
+pub fn two_opt<G: Instancia>(grafo: &G,
+                             solucao: &Solucao<G::Peso>,
+                             candidatos: &[Vec<Vertice>])
+                             -> Solucao<G::Peso> {
+    let n = solucao.caminho().len();
+    if n < 4 {
+        return solucao.clone();
+    }
+    let mut tour = Tour::new(solucao.caminho());
+    let mut fo = solucao.fo();
+    let mut na_fila = vec![true; n];
+    let mut fila = solucao.caminho().iter().cloned().collect::<VecDeque<_>>();
+
+    while let Some(a) = fila.pop_front() {
+        na_fila[a] = false;
+        if let Some(afetados) = melhora_2opt(grafo, &mut tour, &mut fo, &candidatos[a], a) {
+            for &v in &afetados {
+                if !na_fila[v] {
+                    na_fila[v] = true;
+                    fila.push_back(v);
+                }
+            }
+        }
+    }
+
+    Solucao::com_fo(tour.caminho(), fo)
+}
+
+// Procura uma posição que melhore o tour para o trecho de `tamanho` vértices
+// que começa em `inicio`, mantido ou invertido. Devolve o novo caminho e a fo.
+fn melhora_or_opt<G: Instancia>(grafo: &G,
+                                caminho: &Caminho,
+                                posicao: &[usize],
+                                fo: G::Peso,
+                                candidatos: Option<&Candidatos>,
+                                inicio: Vertice,
+                                tamanho: usize)
+                                -> Option<(Caminho, G::Peso)> {
+    let n = caminho.len();
+    let pos = posicao[inicio];
+    let trecho = (0..tamanho).map(|t| caminho[(pos + t) % n]).collect::<Vec<_>>();
+    let (primeiro, ultimo) = (trecho[0], trecho[tamanho - 1]);
+    let antes = caminho[(pos + n - 1) % n];
+    let depois = caminho[(pos + tamanho) % n];
+
+    // Custo interno do trecho nos dois sentidos
+    let mut ida = G::Peso::zero();
+    let mut volta = G::Peso::zero();
+    for par in trecho.windows(2) {
+        ida = ida + grafo.distancia(par[0], par[1]);
+        volta = volta + grafo.distancia(par[1], par[0]);
+    }
+    let retirada = grafo.distancia(antes, primeiro) + grafo.distancia(ultimo, depois);
+    let fechamento = grafo.distancia(antes, depois);
+
+    // Arestas (x, y) onde o trecho pode entrar: todas, ou as que tocam os
+    // candidatos das pontas do trecho
+    let arestas: Vec<(Vertice, Vertice)> = match candidatos {
+        None => (0..n).map(|j| (caminho[j], caminho[(j + 1) % n])).collect(),
+        Some(candidatos) => {
+            candidatos[primeiro]
+                .iter()
+                .chain(&candidatos[ultimo])
+                .flat_map(|&c| {
+                    let p = posicao[c];
+                    vec![(caminho[(p + n - 1) % n], c), (c, caminho[(p + 1) % n])]
+                })
+                .collect()
+        }
+    };
+
+    for (x, y) in arestas {
+        if trecho.contains(&x) || trecho.contains(&y) {
+            continue;
+        }
+        let d_xy = grafo.distancia(x, y);
+        let removidas = retirada + d_xy + ida;
+        let direto = grafo.distancia(x, primeiro) + grafo.distancia(ultimo, y) + ida;
+        let invertido = grafo.distancia(x, ultimo) + grafo.distancia(primeiro, y) + volta;
+        let (adicionadas, inverte) = if invertido.menor(direto) {
+            (invertido + fechamento, true)
+        } else {
+            (direto + fechamento, false)
+        };
+        if !adicionadas.menor(removidas) {
+            continue;
+        }
+
+        // Resto do tour de `depois` até `antes`, com o trecho logo após x
+        let mut novo = Vec::with_capacity(n);
+        for t in 0..n - tamanho {
+            let v = caminho[(pos + tamanho + t) % n];
+            novo.push(v);
+            if v == x {
+                if inverte {
+                    novo.extend(trecho.iter().rev());
+                } else {
+                    novo.extend(&trecho);
+                }
+            }
+        }
+        return Some((novo, fo + adicionadas - removidas));
+    }
+    None
+}
+
+// Or-opt first-improvement: move trechos de 1 a 3 vértices consecutivos para
+// outra posição do tour, também invertidos. Não supõe simetria.
+pub fn or_opt<G: Instancia>(grafo: &G,
+                            solucao: &Solucao<G::Peso>,
+                            candidatos: Option<&Candidatos>)
+                            -> Solucao<G::Peso> {
+    let mut caminho = solucao.caminho().clone();
+    let mut fo = solucao.fo();
+    let n = caminho.len();
+    let mut posicao = vec![0; n];
+    for (i, &v) in caminho.iter().enumerate() {
+        posicao[v] = i;
+    }
+    let mut melhorou = true;
+
+    while melhorou {
+        melhorou = false;
+        for v in 0..n {
+            for tamanho in (1..MAX_TRECHO_OR_OPT + 1).filter(|&t| t + 3 <= n) {
+                let movimento =
+                    melhora_or_opt(grafo, &caminho, &posicao, fo, candidatos, v, tamanho);
+                if let Some((novo, nova_fo)) = movimento {
+                    caminho = novo;
+                    fo = nova_fo;
+                    for (i, &u) in caminho.iter().enumerate() {
+                        posicao[u] = i;
+                    }
+                    melhorou = true;
+                }
+            }
+        }
+    }
+
+    Solucao::com_fo(caminho, fo)
+}
+
+pub fn two_opt_swap(mut caminho: Caminho, i: Vertice, k: Vertice) -> Caminho {
+    caminho[i..k].reverse();
+    caminho
+}
+
+// Custos acumulados ao longo do caminho nos dois sentidos: ida[j] soma as
+// arestas c[0] -> c[1] -> ... -> c[j] e volta[j], as mesmas arestas percorridas
+// ao contrário. Com eles, o custo de inverter um trecho sai em O(1) também em
+// instâncias assimétricas.
+pub fn acumulados<G: Instancia>(grafo: &G, caminho: &Caminho) -> (Vec<G::Peso>, Vec<G::Peso>) {
+    let mut ida = Vec::with_capacity(caminho.len());
+    let mut volta = Vec::with_capacity(caminho.len());
+    ida.push(G::Peso::zero());
+    volta.push(G::Peso::zero());
+    for (j, par) in caminho.windows(2).enumerate() {
+        let (ida_j, volta_j) = (ida[j], volta[j]);
+        ida.push(ida_j + grafo.distancia(par[0], par[1]));
+        volta.push(volta_j + grafo.distancia(par[1], par[0]));
+    }
+    (ida, volta)
+}
+
+// Religações do 3-opt. Com as arestas (a1, a2), (b1, b2) e (c1, c2)
+// removidas, o tour a1 S1 S2 R (S1 = a2..b1, S2 = b2..c1, R = c2..a1) vira:
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Religacao {
+    DoisOpt, // a1 S1' S2 R, sem tocar em (c1, c2)
+    S2S1, // a1 S2 S1 R: or3opt, move um trecho sem inverter nada
+    S2S1i, // a1 S2 S1' R
+    S2iS1, // a1 S2' S1 R
+    S1iS2i, // a1 S1' S2' R
+}
+
+impl Religacao {
+    // Os trechos S1 e S2 na ordem em que entram no novo tour, com o sentido
+    // de cada um
+    pub fn partes<T>(self, s1: T, s2: T) -> [(T, bool); 2] {
+        match self {
+            Religacao::DoisOpt => [(s1, true), (s2, false)],
+            Religacao::S2S1 => [(s2, false), (s1, false)],
+            Religacao::S2S1i => [(s2, false), (s1, true)],
+            Religacao::S2iS1 => [(s2, true), (s1, false)],
+            Religacao::S1iS2i => [(s1, true), (s2, true)],
+        }
+    }
+}
+
+struct Movimento<P: Custo> {
+    direta: bool,
+    religacao: Religacao,
+    q: usize,
+    r: usize,
+    adicionadas: P,
+    removidas: P,
+}
+
+// O tour lido a partir de um vértice, num dos dois sentidos. As posições são
+// relativas a ele: 0 é o próprio vértice, 1 o seguinte, e assim por diante.
+struct Visao<'a> {
+    caminho: &'a Caminho,
+    posicao: &'a [usize],
+    inicio: usize,
+    direta: bool,
+}
+
+impl<'a> Visao<'a> {
+    fn v(&self, rel: usize) -> Vertice {
+        let n = self.caminho.len();
+        let rel = rel % n;
+        if self.direta {
+            self.caminho[(self.inicio + rel) % n]
+        } else {
+            self.caminho[(self.inicio + n - rel) % n]
+        }
+    }
+
+    fn rel(&self, v: Vertice) -> usize {
+        let n = self.caminho.len();
+        if self.direta {
+            (self.posicao[v] + n - self.inicio) % n
+        } else {
+            (self.inicio + n - self.posicao[v]) % n
+        }
+    }
+
+    fn trecho(&self, de: usize, ate: usize, invertido: bool, destino: &mut Caminho) {
+        if invertido {
+            destino.extend((de..ate + 1).rev().map(|rel| self.v(rel)));
+        } else {
+            destino.extend((de..ate + 1).map(|rel| self.v(rel)));
+        }
+    }
+
+    fn religa(&self, religacao: Religacao, q: usize, r: usize) -> Caminho {
+        let n = self.caminho.len();
+        let partes = religacao.partes((1, q), (q + 1, r));
+        let mut novo = Vec::with_capacity(n);
+        novo.push(self.v(0));
+        for &((de, ate), invertido) in &partes {
+            self.trecho(de, ate, invertido, &mut novo);
+        }
+        if r + 1 < n {
+            self.trecho(r + 1, n - 1, false, &mut novo);
+        }
+        novo
+    }
+}
+
+fn considera<P: Custo>(melhor: &mut Option<Movimento<P>>, candidato: Movimento<P>) {
+    if !candidato.adicionadas.menor(candidato.removidas) {
+        return;
+    }
+    let troca = match *melhor {
+        None => true,
+        Some(ref m) => {
+            (candidato.adicionadas + m.removidas).menor(m.adicionadas + candidato.removidas)
+        }
+    };
+    if troca {
+        *melhor = Some(candidato);
+    }
+}
+
+// Melhor movimento 3-opt cuja primeira aresta nova sai de `a1` para um de seus
+// candidatos. A segunda aresta nova liga a2 (ou b1) a um candidato dele, e as
+// buscas param assim que o ganho parcial deixa de ser positivo. Em instâncias
+// assimétricas só o or3opt é válido, e o tour é lido num sentido só.
+fn melhora_3opt<G: Instancia>(grafo: &G,
+                              caminho: &Caminho,
+                              posicao: &[usize],
+                              candidatos: &Candidatos,
+                              simetrica: bool,
+                              a1: Vertice)
+                              -> Option<Movimento<G::Peso>> {
+    let n = caminho.len();
+    let d = |i, j| grafo.distancia(i, j);
+    let sentidos: &[bool] = if simetrica { &[true, false] } else { &[true] };
+    let mut melhor = None;
+
+    for &direta in sentidos {
+        let visao = Visao {
+            caminho: caminho,
+            posicao: posicao,
+            inicio: posicao[a1],
+            direta: direta,
+        };
+        let a2 = visao.v(1);
+        let d_a1a2 = d(a1, a2);
+        let movimento = |religacao, q, r, adicionadas, removidas| {
+            Movimento {
+                direta: direta,
+                religacao: religacao,
+                q: q,
+                r: r,
+                adicionadas: adicionadas,
+                removidas: removidas,
+            }
+        };
+
+        for &x in &candidatos[a1] {
+            let d_a1x = d(a1, x);
+            if !d_a1x.menor(d_a1a2) {
+                break;
+            }
+            let rx = visao.rel(x);
+
+            // x como b1
+            if simetrica && rx >= 2 && rx + 2 <= n {
+                let b2 = visao.v(rx + 1);
+                let removidas = d_a1a2 + d(x, b2);
+                considera(&mut melhor,
+                          movimento(Religacao::DoisOpt, rx, n - 1, d_a1x + d(a2, b2), removidas));
+                for &c1 in &candidatos[a2] {
+                    let parcial = d_a1x + d(a2, c1);
+                    if !parcial.menor(removidas) {
+                        break;
+                    }
+                    let r = visao.rel(c1);
+                    if r > rx {
+                        let c2 = visao.v(r + 1);
+                        considera(&mut melhor,
+                                  movimento(Religacao::S1iS2i,
+                                            rx,
+                                            r,
+                                            parcial + d(b2, c2),
+                                            removidas + d(c1, c2)));
+                    }
+                }
+            }
+
+            // x como b2
+            if rx >= 2 {
+                let b1 = visao.v(rx - 1);
+                let removidas = d_a1a2 + d(b1, x);
+                for &c1 in &candidatos[a2] {
+                    if !(d_a1x + d(a2, c1)).menor(removidas) {
+                        break;
+                    }
+                    let r = visao.rel(c1);
+                    if r >= rx {
+                        let c2 = visao.v(r + 1);
+                        considera(&mut melhor,
+                                  movimento(Religacao::S2S1,
+                                            rx - 1,
+                                            r,
+                                            d_a1x + d(c1, a2) + d(b1, c2),
+                                            removidas + d(c1, c2)));
+                    }
+                }
+                if simetrica {
+                    for &c1 in &candidatos[b1] {
+                        let parcial = d_a1x + d(b1, c1);
+                        if !parcial.menor(removidas) {
+                            break;
+                        }
+                        let r = visao.rel(c1);
+                        if r >= rx {
+                            let c2 = visao.v(r + 1);
+                            considera(&mut melhor,
+                                      movimento(Religacao::S2S1i,
+                                                rx - 1,
+                                                r,
+                                                parcial + d(a2, c2),
+                                                removidas + d(c1, c2)));
+                        }
+                    }
+                }
+            }
+
+            // x como c1
+            if simetrica && rx >= 2 {
+                let c2 = visao.v(rx + 1);
+                let removidas = d_a1a2 + d(x, c2);
+                for &b2 in &candidatos[a2] {
+                    let parcial = d_a1x + d(a2, b2);
+                    if !parcial.menor(removidas) {
+                        break;
+                    }
+                    let q = visao.rel(b2);
+                    if q >= 2 && q <= rx {
+                        let b1 = visao.v(q - 1);
+                        considera(&mut melhor,
+                                  movimento(Religacao::S2iS1,
+                                            q - 1,
+                                            rx,
+                                            parcial + d(b1, c2),
+                                            removidas + d(b1, b2)));
+                    }
+                }
+            }
+        }
+    }
+    melhor
+}
+
+// 3-opt restrito às listas de candidatos, com don't-look bits. Cada vértice
+// aplica o melhor dos seus movimentos; o caminho é remontado a cada melhora.
+pub fn three_opt<G: Instancia>(grafo: &G,
+                               solucao: &Solucao<G::Peso>,
+                               candidatos: &Candidatos,
+                               simetrica: bool)
+                               -> Solucao<G::Peso> {
+    let n = solucao.caminho().len();
+    if n < 5 {
+        return solucao.clone();
+    }
+    let mut caminho = solucao.caminho().clone();
+    let mut fo = solucao.fo();
+    let mut posicao = vec![0; n];
+    for (i, &v) in caminho.iter().enumerate() {
+        posicao[v] = i;
+    }
+    let mut na_fila = vec![true; n];
+    let mut fila = caminho.iter().cloned().collect::<VecDeque<_>>();
+
+    while let Some(a) = fila.pop_front() {
+        na_fila[a] = false;
+        let m = match melhora_3opt(grafo, &caminho, &posicao, candidatos, simetrica, a) {
+            Some(m) => m,
+            None => continue,
+        };
+        let (novo, afetados) = {
+            let visao = Visao {
+                caminho: &caminho,
+                posicao: &posicao,
+                inicio: posicao[a],
+                direta: m.direta,
+            };
+            let afetados = [1, m.q, m.q + 1, m.r, m.r + 1]
+                .iter()
+                .map(|&rel| visao.v(rel))
+                .collect::<Vec<_>>();
+            (visao.religa(m.religacao, m.q, m.r), afetados)
+        };
+        caminho = novo;
+        fo = fo + m.adicionadas - m.removidas;
+        for (i, &v) in caminho.iter().enumerate() {
+            posicao[v] = i;
+        }
+        for v in afetados.into_iter().chain(Some(a)) {
+            if !na_fila[v] {
+                na_fila[v] = true;
+                fila.push_back(v);
+            }
+        }
+    }
+
+    Solucao::com_fo(caminho, fo)
+}
+
+// Listas de candidatos e simetria da instância, calculadas uma vez e
+// reaproveitadas em todas as buscas locais
+pub struct Contexto {
+    candidatos: Option<Candidatos>,
+    candidatos_padrao: Option<Candidatos>,
+    simetrica: bool,
+}
+
+impl Contexto {
+    pub fn new<G: Instancia>(grafo: &G,
+                             vizinhancas: &[Vizinhanca],
+                             candidatos: Option<(TipoCandidatos, usize)>)
+                             -> Contexto {
+        let simetrica = diagnostico::simetrica(grafo);
+        // As listas de candidatos só valem para o 2-opt simétrico; nas
+        // instâncias assimétricas a busca local continua completa
+        let candidatos = match candidatos {
+            Some((tipo, k)) if simetrica => Some(candidatos::calcula(grafo, k, tipo)),
+            _ => None,
+        };
+        // 3-opt e LK sempre usam listas; sem configuração, ficam com os mais
+        // próximos
+        let precisa = vizinhancas.iter()
+            .any(|&v| v == Vizinhanca::ThreeOpt || v == Vizinhanca::LinKernighan);
+        let candidatos_padrao = if candidatos.is_none() && precisa {
+            Some(candidatos::mais_proximos(grafo, CANDIDATOS_PADRAO))
+        } else {
+            None
+        };
+        Contexto {
+            candidatos: candidatos,
+            candidatos_padrao: candidatos_padrao,
+            simetrica: simetrica,
+        }
+    }
+
+    // Listas configuradas, se houver
+    pub fn candidatos(&self) -> Option<&Candidatos> {
+        self.candidatos.as_ref()
+    }
+
+    // Listas de quem sempre as usa: as configuradas ou as dos mais próximos
+    pub fn listas(&self) -> &Candidatos {
+        self.candidatos.as_ref().or(self.candidatos_padrao.as_ref()).unwrap()
+    }
+
+    pub fn simetrica(&self) -> bool {
+        self.simetrica
+    }
+}
+
+// VND: desce em cada vizinhança até um ótimo local, voltando à primeira
+// sempre que outra delas melhora o tour. Cada vizinhança desce pela sua
+// estrutura (`vizinhanca::Estrutura`).
+pub struct Vnd {
+    vizinhancas: Vec<Vizinhanca>,
+    contexto: Contexto,
+}
+
+impl Vnd {
+    pub fn new<G: Instancia>(grafo: &G,
+                             vizinhancas: &[Vizinhanca],
+                             candidatos: Option<(TipoCandidatos, usize)>)
+                             -> Vnd {
+        Vnd {
+            vizinhancas: vizinhancas.to_vec(),
+            contexto: Contexto::new(grafo, vizinhancas, candidatos),
+        }
+    }
+
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           vizinhanca: Vizinhanca,
+                           solucao: &Solucao<G::Peso>)
+                           -> Solucao<G::Peso> {
+        let contexto = &self.contexto;
+        match vizinhanca {
+            Vizinhanca::TwoOpt => DoisOpt.desce(grafo, solucao, contexto),
+            Vizinhanca::OrOpt => OrOpt.desce(grafo, solucao, contexto),
+            Vizinhanca::ThreeOpt => TresOpt.desce(grafo, solucao, contexto),
+            Vizinhanca::LinKernighan => LinKernighan.desce(grafo, solucao, contexto),
+            Vizinhanca::Troca => Troca.desce(grafo, solucao, contexto),
+            Vizinhanca::Insercao => Insercao.desce(grafo, solucao, contexto),
+        }
+    }
+
+    pub fn aplica<G: Instancia>(&self, grafo: &G, solucao: &Solucao<G::Peso>) -> Solucao<G::Peso> {
+        let mut atual = solucao.clone();
+        let mut k = 0;
+        while k < self.vizinhancas.len() {
+            let nova = self.desce(grafo, self.vizinhancas[k], &atual);
+            if nova.fo().menor(atual.fo()) {
+                atual = nova;
+                k = if k == 0 { 1 } else { 0 };
+            } else {
+                k += 1;
+            }
+        }
+        atual
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_fo, confere_permutacao, confere_qualidade, matriz_aleatoria,
+                 pontos_aleatorios, solucao_aleatoria};
+    use vizinhanca;
+
+    // Versão anterior, que recalcula o custo do tour inteiro para cada par
+    fn two_opt_referencia(grafo: &Grafo, solucao: &Solucao) -> Option<Solucao> {
+        let n = solucao.caminho().len();
+        let mut best = solucao.clone();
+        for i in 0..n - 1 {
+            for k in i + 1..n {
+                let nova = Solucao::new(grafo, two_opt_swap(solucao.caminho().clone(), i, k));
+                if nova.fo().menor(best.fo()) {
+                    best = nova;
+                }
+            }
+        }
+        if best.fo().menor(solucao.fo()) { Some(best) } else { None }
+    }
+
+    fn mesma_busca(grafo: &Grafo) {
+        let mut atual = solucao_aleatoria(grafo);
+        loop {
+            let esperado = two_opt_referencia(grafo, &atual);
+            let obtido = vizinhanca::melhor_movimento(grafo, &DoisOpt, atual.caminho())
+                .map(|(movimento, removidas, adicionadas)| {
+                    Solucao::com_fo(DoisOpt.aplica(atual.caminho(), movimento),
+                                    atual.fo() + adicionadas - removidas)
+                });
+            match (esperado, obtido) {
+                (Some(e), Some(o)) => {
+                    assert_eq!(e.caminho(), o.caminho());
+                    assert_eq!(e.fo(), o.fo());
+                    atual = o;
+                }
+                (None, None) => break,
+                _ => panic!("movimentos diferentes"),
+            }
+        }
+    }
+
+    #[test]
+    fn delta_igual_avaliacao_completa() {
+        mesma_busca(&Grafo::from_arquivo("instances/p01.txt").unwrap());
+        mesma_busca(&Grafo::from_tsplib("instances/gr17.tsp").unwrap());
+    }
+
+    #[test]
+    fn delta_assimetrica() {
+        mesma_busca(&matriz_aleatoria(15, false));
+    }
+
+    #[test]
+    fn inversao_mantem_posicoes() {
+        let mut tour = Tour::new(&vec![4, 2, 0, 1, 3, 5]);
+        tour.inverte(0, 3);
+        assert_eq!(tour.caminho(), vec![4, 2, 3, 1, 0, 5]);
+        // Trecho que dá a volta: inverte o complemento
+        tour.inverte(0, 2);
+        assert_eq!(tour.caminho(), vec![4, 2, 1, 3, 0, 5]);
+        for v in 0..6 {
+            assert_eq!(tour.ordem[tour.posicao[v]], v);
+            assert_eq!(tour.antecessor(tour.sucessor(v)), v);
+        }
+    }
+
+    #[test]
+    fn fo_incremental_correta() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(10);
+        for _ in 0..10 {
+            let s = two_opt(&g, &solucao_aleatoria(&g), &candidatos);
+            confere_qualidade(&g, &s, OTIMO_ATT48, 15);
+        }
+    }
+
+    #[test]
+    fn or_opt_assimetrica() {
+        // Ciclo 0 -> 1 -> ... -> 7 custa 8; os outros arcos custam 10
+        let n = 8;
+        let m = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| match j {
+                        _ if j == i => 0,
+                        _ if j == (i + 1) % n => 1,
+                        _ => 10,
+                    })
+                    .collect()
+            })
+            .collect();
+        let g = Grafo::from_matriz(m);
+        for _ in 0..10 {
+            let s = or_opt(&g, &solucao_aleatoria(&g), None);
+            confere_fo(&g, &s);
+        }
+        // Um vértice fora do lugar é corrigido em um movimento
+        let s = or_opt(&g, &Solucao::new(&g, vec![0, 2, 1, 3, 4, 5, 6, 7]), None);
+        assert_eq!(s.fo(), 8);
+    }
+
+    #[test]
+    fn or_opt_com_candidatos() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(10);
+        for _ in 0..5 {
+            let inicial = two_opt(&g, &solucao_aleatoria(&g), &candidatos);
+            let completo = or_opt(&g, &inicial, None);
+            let restrito = or_opt(&g, &inicial, Some(&candidatos));
+            assert!(completo.fo() <= inicial.fo() && restrito.fo() <= inicial.fo());
+            confere_fo(&g, &restrito);
+            confere_fo(&g, &completo);
+        }
+    }
+
+    #[test]
+    fn instancia_grande() {
+        let g = pontos_aleatorios(3000);
+        let candidatos = g.candidatos_quadrantes(8).unwrap();
+        let inicial = solucao_aleatoria(&g);
+        let s = two_opt(&g, &inicial, &candidatos);
+        confere_fo(&g, &s);
+        // Tour ótimo esperado: ~0,7124 √(n A) ≈ 390000
+        assert!(s.fo() < 500000);
+    }
+
+    #[test]
+    fn three_opt_fo_incremental() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(10);
+        for _ in 0..10 {
+            let inicial = solucao_aleatoria(&g);
+            let s = three_opt(&g, &inicial, &candidatos, true);
+            confere_fo(&g, &s);
+            confere_permutacao(s.caminho(), 48);
+
+            // Partindo de um ótimo local do 2-opt, o 3-opt não piora
+            let s = two_opt(&g, &inicial, &candidatos);
+            let t = three_opt(&g, &s, &candidatos, true);
+            assert!(t.fo() <= s.fo());
+            confere_fo(&g, &t);
+        }
+    }
+
+    #[test]
+    fn or3opt_assimetrica() {
+        let g = matriz_aleatoria(20, false);
+        let candidatos = g.candidatos(8);
+        for _ in 0..10 {
+            let inicial = solucao_aleatoria(&g);
+            let s = three_opt(&g, &inicial, &candidatos, false);
+            assert!(s.fo() <= inicial.fo());
+            confere_fo(&g, &s);
+        }
+    }
+
+    #[test]
+    fn vnd_escolhe_vizinhancas() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let inicial = solucao_aleatoria(&g);
+        let so_2opt = Vnd::new(&g, &[Vizinhanca::TwoOpt], None).aplica(&g, &inicial);
+        let vnd = Vnd::new(&g, &[Vizinhanca::TwoOpt, Vizinhanca::ThreeOpt], None);
+        let s = vnd.aplica(&g, &inicial);
+        // O 2-opt completo é determinístico: o VND parte do mesmo ótimo local
+        assert!(s.fo() <= so_2opt.fo());
+        confere_fo(&g, &s);
+    }
+}
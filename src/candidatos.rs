@@ -0,0 +1,154 @@
+use grafo::{Grafo, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+
+// Listas de candidatos: para cada vértice, os vizinhos considerados pelas
+// buscas locais, do mais próximo ao mais distante
+pub type Candidatos = Vec<Vec<Vertice>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TipoCandidatos {
+    MaisProximos,
+    // k / 4 vizinhos em cada quadrante ao redor do vértice, completados pelos
+    // mais próximos. Evita que vértices numa região densa só vejam a própria região.
+    Quadrantes,
+}
+
+// Mantém os `k` pares de menor peso, em ordem crescente
+fn insere<P: Custo>(lista: &mut Vec<(Vertice, P)>, k: usize, vertice: Vertice, peso: P) {
+    if k == 0 || (lista.len() == k && !peso.menor(lista[k - 1].1)) {
+        return;
+    }
+    let pos = lista.iter().position(|&(_, p)| peso.menor(p)).unwrap_or(lista.len());
+    lista.insert(pos, (vertice, peso));
+    lista.truncate(k);
+}
+
+pub fn mais_proximos<G: Instancia>(grafo: &G, k: usize) -> Candidatos {
+    let n = grafo.num_vertices();
+    (0..n)
+        .map(|i| {
+            let mut lista = Vec::with_capacity(k + 1);
+            for j in (0..n).filter(|&j| grafo.aresta(i, j)) {
+                insere(&mut lista, k, j, grafo.distancia(i, j));
+            }
+            lista.into_iter().map(|(j, _)| j).collect()
+        })
+        .collect()
+}
+
+// Sem coordenadas, não há quadrantes: devolve None
+pub fn quadrantes<G: Instancia>(grafo: &G, k: usize) -> Option<Candidatos> {
+    let pontos = match grafo.coordenadas() {
+        Some(c) => c.pontos(),
+        None => return None,
+    };
+    let n = grafo.num_vertices();
+    let por_quadrante = (k / 4).max(1);
+
+    let candidatos = (0..n)
+        .map(|i| {
+            let mut quadrantes = vec![Vec::with_capacity(por_quadrante + 1); 4];
+            let mut proximos = Vec::with_capacity(k + 1);
+            for j in (0..n).filter(|&j| grafo.aresta(i, j)) {
+                let peso = grafo.distancia(i, j);
+                let q = match (pontos[j].x >= pontos[i].x, pontos[j].y >= pontos[i].y) {
+                    (true, true) => 0,
+                    (false, true) => 1,
+                    (false, false) => 2,
+                    (true, false) => 3,
+                };
+                insere(&mut quadrantes[q], por_quadrante, j, peso);
+                insere(&mut proximos, k, j, peso);
+            }
+
+            let mut lista = quadrantes.into_iter().flat_map(|q| q).collect::<Vec<_>>();
+            for (j, peso) in proximos {
+                if lista.len() >= k {
+                    break;
+                }
+                if lista.iter().all(|&(v, _)| v != j) {
+                    lista.push((j, peso));
+                }
+            }
+            lista.sort_by(|&(_, a), &(_, b)| a.compara(b));
+            lista.truncate(k);
+            lista.into_iter().map(|(j, _)| j).collect()
+        })
+        .collect();
+    Some(candidatos)
+}
+
+// Quadrantes só existem em instâncias com coordenadas; nas demais, usa os
+// mais próximos
+pub fn calcula<G: Instancia>(grafo: &G, k: usize, tipo: TipoCandidatos) -> Candidatos {
+    match tipo {
+        TipoCandidatos::MaisProximos => mais_proximos(grafo, k),
+        TipoCandidatos::Quadrantes => {
+            quadrantes(grafo, k).unwrap_or_else(|| mais_proximos(grafo, k))
+        }
+    }
+}
+
+impl<P: Custo> Grafo<P> {
+    // Os `k` vizinhos mais próximos de cada vértice
+    #[allow(dead_code)]
+    pub fn candidatos(&self, k: usize) -> Candidatos {
+        mais_proximos(self, k)
+    }
+
+    #[allow(dead_code)]
+    pub fn candidatos_quadrantes(&self, k: usize) -> Option<Candidatos> {
+        quadrantes(self, k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use coordenadas::{Coordenadas, Metrica, Ponto};
+
+    #[test]
+    fn vizinhos_ordenados() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(8);
+        for (i, lista) in candidatos.iter().enumerate() {
+            assert_eq!(lista.len(), 8);
+            assert!(!lista.contains(&i));
+            assert!(lista.windows(2).all(|p| g.distancia(i, p[0]) <= g.distancia(i, p[1])));
+            // Nenhum vértice fora da lista é mais próximo que o último dela
+            let ultimo = g.distancia(i, lista[7]);
+            assert!((0..48)
+                .filter(|&j| j != i && !lista.contains(&j))
+                .all(|j| g.distancia(i, j) >= ultimo));
+        }
+    }
+
+    #[test]
+    fn ignora_arestas_inf() {
+        let g = Grafo::toy();
+        for (i, lista) in g.candidatos(10).iter().enumerate() {
+            assert!(lista.iter().all(|&j| g.aresta(i, j)));
+        }
+    }
+
+    // Dois aglomerados distantes: os mais próximos ficam todos no próprio
+    // aglomerado, os quadrantes alcançam o outro
+    #[test]
+    fn quadrantes_alcancam_outra_regiao() {
+        let pontos = (0..20)
+            .map(|i| {
+                Ponto {
+                    x: if i < 10 { i as f64 } else { 1000.0 + i as f64 },
+                    y: (i % 3) as f64,
+                    z: 0.0,
+                }
+            })
+            .collect();
+        let g: Grafo = Grafo::from_coordenadas(Coordenadas::new(pontos, Metrica::Euc2d));
+        assert!(g.candidatos(8)[9].iter().all(|&j| j < 10));
+        assert!(g.candidatos_quadrantes(8).unwrap()[9].iter().any(|&j| j >= 10));
+        assert!(Grafo::toy().candidatos_quadrantes(8).is_none());
+    }
+}
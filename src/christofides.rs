@@ -0,0 +1,251 @@
+use std::f64;
+use std::mem;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use diagnostico;
+use emparelhamento;
+use erro::ErroConstrucao;
+
+// 1 GiB
+const LIMITE_MEMORIA_PADRAO: usize = 1 << 30;
+
+// Memória da matriz de custos mais a do emparelhamento no pior caso, com todos
+// os vértices de grau ímpar: até n² / 2 arestas, cada uma com duas entradas
+// nas listas de adjacência
+fn memoria_necessaria(num_vertices: usize) -> Option<usize> {
+    let aresta = mem::size_of::<(Vertice, Vertice, f64)>() + 2 * mem::size_of::<usize>();
+    let por_par = mem::size_of::<f64>() + aresta / 2;
+    num_vertices.checked_mul(num_vertices).and_then(|x| x.checked_mul(por_par))
+}
+
+// Árvore geradora mínima (Prim), como lista de arestas
+fn arvore_geradora(n: usize, custos: &[Vec<f64>]) -> Vec<(Vertice, Vertice)> {
+    let mut na_arvore = vec![false; n];
+    let mut menor = vec![f64::INFINITY; n];
+    let mut pai = vec![0; n];
+    let mut arestas = Vec::with_capacity(n - 1);
+    menor[0] = 0.0;
+
+    for _ in 0..n {
+        let u = (0..n)
+            .filter(|&v| !na_arvore[v])
+            .min_by(|&a, &b| menor[a].partial_cmp(&menor[b]).unwrap())
+            .unwrap();
+        na_arvore[u] = true;
+        if u != 0 {
+            arestas.push((pai[u], u));
+        }
+        for v in 0..n {
+            if !na_arvore[v] && custos[u][v] < menor[v] {
+                menor[v] = custos[u][v];
+                pai[v] = u;
+            }
+        }
+    }
+    arestas
+}
+
+// Circuito euleriano do multigrafo (Hierholzer), partindo do vértice 0
+fn circuito_euleriano(n: usize, arestas: &[(Vertice, Vertice)]) -> Vec<Vertice> {
+    let mut incidentes = vec![vec![]; n];
+    for (k, &(i, j)) in arestas.iter().enumerate() {
+        incidentes[i].push(k);
+        incidentes[j].push(k);
+    }
+    let mut usada = vec![false; arestas.len()];
+    let mut proxima = vec![0; n];
+    let mut circuito = Vec::with_capacity(arestas.len() + 1);
+    let mut pilha = vec![0];
+
+    while let Some(&v) = pilha.last() {
+        while proxima[v] < incidentes[v].len() && usada[incidentes[v][proxima[v]]] {
+            proxima[v] += 1;
+        }
+        if proxima[v] == incidentes[v].len() {
+            circuito.push(v);
+            pilha.pop();
+        } else {
+            let k = incidentes[v][proxima[v]];
+            usada[k] = true;
+            let (i, j) = arestas[k];
+            pilha.push(if i == v { j } else { i });
+        }
+    }
+    circuito
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           exige_metrica: bool,
+                           limite_memoria: usize)
+                           -> Result<Solucao<G::Peso>, ErroConstrucao> {
+    let n = grafo.num_vertices();
+    let necessaria = memoria_necessaria(n).unwrap_or(usize::max_value());
+    if necessaria > limite_memoria {
+        return Err(ErroConstrucao::MemoriaInsuficiente {
+            necessaria: necessaria,
+            limite: limite_memoria,
+        });
+    }
+
+    let diag = diagnostico::analisa(grafo);
+    if !diag.hamiltoniano_possivel() {
+        return Err(ErroConstrucao::Infactivel("graph of finite edges cannot hold a Hamiltonian \
+                                               cycle"
+            .to_string()));
+    }
+    if !diag.simetrica || diag.arestas_inf > 0 {
+        return Err(ErroConstrucao::NaoSuportada("Christofides needs a complete graph with \
+                                                 symmetric weights"
+            .to_string()));
+    }
+    // Acima do limite do diagnóstico a desigualdade não é verificada, e o
+    // algoritmo roda sem a garantia
+    if exige_metrica && diag.desigualdade_triangular == Some(false) {
+        return Err(ErroConstrucao::NaoMetrica { violacoes: diag.num_violacoes });
+    }
+
+    if n <= 3 {
+        return Ok(Solucao::new(grafo, (0..n).collect()));
+    }
+
+    let custos = (0..n)
+        .map(|i| (0..n).map(|j| grafo.distancia(i, j).to_f64()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut arestas = arvore_geradora(n, &custos);
+
+    let mut grau = vec![0; n];
+    for &(i, j) in &arestas {
+        grau[i] += 1;
+        grau[j] += 1;
+    }
+    let impares = (0..n).filter(|&v| grau[v] % 2 == 1).collect::<Vec<_>>();
+    let par = emparelhamento::perfeito_minimo(impares.len(),
+                                              |a, b| custos[impares[a]][impares[b]]);
+    for (a, &b) in par.iter().enumerate() {
+        if a < b {
+            arestas.push((impares[a], impares[b]));
+        }
+    }
+
+    // Atalhos: cada vértice entra no tour na primeira vez em que aparece
+    let mut visitado = vec![false; n];
+    let mut caminho: Caminho = Vec::with_capacity(n);
+    for v in circuito_euleriano(n, &arestas) {
+        if !visitado[v] {
+            visitado[v] = true;
+            caminho.push(v);
+        }
+    }
+    Ok(Solucao::new(grafo, caminho))
+}
+
+// Aproximação de Christofides: em instâncias métricas, o tour custa no máximo
+// 1,5 vez o ótimo
+pub struct Christofides<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    exige_metrica: bool,
+    limite_memoria: usize,
+}
+
+impl<'a, G: Instancia> Christofides<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Christofides<G> {
+        Christofides {
+            grafo: grafo,
+            exige_metrica: true,
+            limite_memoria: LIMITE_MEMORIA_PADRAO,
+        }
+    }
+
+    // Sem a exigência, roda mesmo quando a desigualdade triangular é violada,
+    // mas o tour perde a garantia de qualidade
+    #[allow(dead_code)]
+    pub fn exige_metrica(&mut self, exige_metrica: bool) -> &mut Christofides<'a, G> {
+        self.exige_metrica = exige_metrica;
+        self
+    }
+
+    // Limite, em bytes, para a matriz de custos e o emparelhamento
+    #[allow(dead_code)]
+    pub fn limite_memoria(&mut self, limite_memoria: usize) -> &mut Christofides<'a, G> {
+        self.limite_memoria = limite_memoria;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<Solucao<G::Peso>, ErroConstrucao> {
+        solve(self.grafo, self.exige_metrica, self.limite_memoria)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Peso};
+    use instancia::FnInstancia;
+
+    fn fo(arquivo: &str, exige_metrica: bool) -> Peso {
+        let g: Grafo = Grafo::from_tsplib(arquivo).unwrap();
+        Christofides::new(&g).exige_metrica(exige_metrica).solve().unwrap().fo()
+    }
+
+    #[test]
+    fn garantia_de_aproximacao() {
+        let fo = fo("instances/att48.tsp", true);
+        assert!(fo >= 10628 && 2 * fo <= 3 * 10628);
+    }
+
+    // Violações pequenas, vindas do arredondamento das distâncias
+    #[test]
+    fn quase_metricas() {
+        for &(arquivo, otimo) in &[("instances/gr17.tsp", 2085),
+                                   ("instances/fri26.tsp", 937),
+                                   ("instances/dantzig42.tsp", 699)] {
+            let fo = fo(arquivo, false);
+            assert!(fo >= otimo && 2 * fo <= 3 * otimo, "{}: {}", arquivo, fo);
+        }
+    }
+
+    #[test]
+    fn pesos_reais() {
+        let g = FnInstancia::new(10, |i, j| 0.5 * (i as f64 - j as f64).abs());
+        let s = Christofides::new(&g).solve().unwrap();
+        assert!(s.fo().igual(9.0));
+    }
+
+    #[test]
+    fn recusa_nao_metrica() {
+        let g = Grafo::toy();
+        match Christofides::new(&g).solve() {
+            Err(ErroConstrucao::NaoMetrica { violacoes }) => assert!(violacoes > 0),
+            _ => panic!("esperava instância não métrica"),
+        }
+        let s = Christofides::new(&g).exige_metrica(false).solve().unwrap();
+        assert_eq!(s.caminho().len(), g.num_vertices());
+    }
+
+    #[test]
+    fn recusa_assimetrica() {
+        let g = Grafo::from_matriz(vec![vec![0, 1, 2, 2],
+                                        vec![2, 0, 1, 2],
+                                        vec![2, 2, 0, 1],
+                                        vec![1, 2, 2, 0]]);
+        match Christofides::new(&g).solve() {
+            Err(ErroConstrucao::NaoSuportada(_)) => {}
+            _ => panic!("esperava instância não suportada"),
+        }
+    }
+
+    #[test]
+    fn recusa_acima_do_limite_de_memoria() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        match Christofides::new(&g).limite_memoria(48 * 48).solve() {
+            Err(ErroConstrucao::MemoriaInsuficiente { necessaria, limite }) => {
+                assert!(necessaria > limite)
+            }
+            _ => panic!("esperava falta de memória"),
+        }
+    }
+}
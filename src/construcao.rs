@@ -0,0 +1,591 @@
+extern crate rand;
+
+use std::cmp::Ordering;
+use std::f64;
+use self::rand::Rng;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use diagnostico;
+use christofides::Christofides;
+use erro::ErroConstrucao;
+
+pub const MAX_TENTATIVAS: u32 = 1000;
+const LIMITE_NOS_DFS: u64 = 1_000_000;
+
+// Tenta o construtor aleatório até MAX_TENTATIVAS vezes e, se nenhuma tentativa
+// evitar arestas INF, recorre à busca em profundidade com backtracking
+pub fn com_retentativas<R, G, F>(rng: &mut R,
+                                 grafo: &G,
+                                 mut construtor: F)
+                                 -> Result<Caminho, ErroConstrucao>
+    where R: Rng,
+          G: Instancia,
+          F: FnMut(&mut R) -> Option<Caminho>
+{
+    for _ in 0..MAX_TENTATIVAS {
+        if let Some(caminho) = construtor(rng) {
+            return Ok(caminho);
+        }
+    }
+
+    dfs_backtracking(rng, grafo, LIMITE_NOS_DFS)
+        .ok_or(ErroConstrucao::DificilConstruir { tentativas: MAX_TENTATIVAS })
+}
+
+// Candidatos a seguir `atual`, em ordem de prioridade reversa (o último é
+// tentado primeiro): vértices com menos vizinhos livres vêm antes (Warnsdorff)
+fn candidatos<R, G>(rng: &mut R, grafo: &G, atual: Vertice, marcados: &[bool]) -> Vec<Vertice>
+    where R: Rng,
+          G: Instancia
+{
+    let n = grafo.num_vertices();
+    let mut cands = (0..n)
+        .filter(|&v| !marcados[v] && grafo.aresta(atual, v))
+        .collect::<Vec<_>>();
+    rng.shuffle(&mut cands);
+
+    let livres = |v: Vertice| (0..n).filter(|&w| !marcados[w] && grafo.aresta(v, w)).count();
+    cands.sort_by_key(|&v| n - livres(v));
+    cands
+}
+
+pub fn dfs_backtracking<R, G>(rng: &mut R, grafo: &G, limite_nos: u64) -> Option<Caminho>
+    where R: Rng,
+          G: Instancia
+{
+    let n = grafo.num_vertices();
+    if n == 0 {
+        return None;
+    }
+
+    let inicial = rng.gen::<Vertice>() % n;
+    let mut marcados = vec![false; n];
+    let mut caminho = Vec::with_capacity(n);
+    marcados[inicial] = true;
+    caminho.push(inicial);
+
+    // opcoes[d]: candidatos ainda não tentados para a posição d + 1
+    let mut opcoes = vec![candidatos(rng, grafo, inicial, &marcados)];
+    let mut nos = 0;
+
+    while !opcoes.is_empty() {
+        if caminho.len() == n && (n == 1 || grafo.aresta(caminho[n - 1], inicial)) {
+            return Some(caminho);
+        }
+
+        nos += 1;
+        if nos > limite_nos {
+            return None;
+        }
+
+        let topo = opcoes.len() - 1;
+        match opcoes[topo].pop() {
+            Some(prox) => {
+                marcados[prox] = true;
+                caminho.push(prox);
+                let cands = candidatos(rng, grafo, prox, &marcados);
+                opcoes.push(cands);
+            }
+            None => {
+                opcoes.pop();
+                if let Some(vert) = caminho.pop() {
+                    marcados[vert] = false;
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Heurísticas construtivas clássicas. Todas supõem um grafo completo: em grafos
+// esparsos o tour pode usar arestas INF.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Heuristica {
+    VizinhoMaisProximo,
+    InsercaoMaisProxima,
+    InsercaoMaisDistante,
+    InsercaoMaisBarata,
+    InsercaoAleatoria,
+    Economias,
+    ArestaGulosa,
+    CurvaHilbert,
+    Christofides,
+}
+
+impl Heuristica {
+    pub fn from_nome(s: &str) -> Option<Heuristica> {
+        Some(match s {
+            "vizinho" => Heuristica::VizinhoMaisProximo,
+            "insercao-proxima" => Heuristica::InsercaoMaisProxima,
+            "insercao-distante" => Heuristica::InsercaoMaisDistante,
+            "insercao-barata" => Heuristica::InsercaoMaisBarata,
+            "insercao-aleatoria" => Heuristica::InsercaoAleatoria,
+            "economias" => Heuristica::Economias,
+            "aresta-gulosa" => Heuristica::ArestaGulosa,
+            "hilbert" => Heuristica::CurvaHilbert,
+            "christofides" => Heuristica::Christofides,
+            _ => return None,
+        })
+    }
+}
+
+pub fn constroi<R, G>(rng: &mut R,
+                      grafo: &G,
+                      heuristica: Heuristica)
+                      -> Result<Solucao<G::Peso>, ErroConstrucao>
+    where R: Rng,
+          G: Instancia
+{
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let caminho = match heuristica {
+        Heuristica::VizinhoMaisProximo => vizinho_mais_proximo(grafo, 0),
+        Heuristica::InsercaoMaisProxima => insercao(rng, grafo, Heuristica::InsercaoMaisProxima),
+        Heuristica::InsercaoMaisDistante => insercao(rng, grafo, Heuristica::InsercaoMaisDistante),
+        Heuristica::InsercaoMaisBarata => insercao_mais_barata(grafo),
+        Heuristica::InsercaoAleatoria => insercao(rng, grafo, Heuristica::InsercaoAleatoria),
+        Heuristica::Economias => economias(grafo, 0),
+        Heuristica::ArestaGulosa => aresta_gulosa(grafo),
+        Heuristica::CurvaHilbert => curva_hilbert(grafo)?,
+        Heuristica::Christofides => return Christofides::new(grafo).exige_metrica(false).solve(),
+    };
+    Ok(Solucao::new(grafo, caminho))
+}
+
+fn d<G: Instancia>(grafo: &G, i: Vertice, j: Vertice) -> f64 {
+    grafo.distancia(i, j).to_f64()
+}
+
+fn compara(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+pub fn vizinho_mais_proximo<G: Instancia>(grafo: &G, inicial: Vertice) -> Caminho {
+    let n = grafo.num_vertices();
+    let mut marcados = vec![false; n];
+    let mut caminho = Vec::with_capacity(n);
+    caminho.push(inicial);
+    marcados[inicial] = true;
+
+    while caminho.len() < n {
+        let atual = caminho[caminho.len() - 1];
+        let prox = (0..n)
+            .filter(|&v| !marcados[v])
+            .min_by(|&a, &b| grafo.distancia(atual, a).compara(grafo.distancia(atual, b)))
+            .unwrap();
+        marcados[prox] = true;
+        caminho.push(prox);
+    }
+    caminho
+}
+
+// Tour como lista ligada de sucessores, a partir de `inicio`
+fn caminho_de_sucessores(prox: &[Vertice], inicio: Vertice, tamanho: usize) -> Caminho {
+    let mut caminho = Vec::with_capacity(tamanho);
+    let mut v = inicio;
+    for _ in 0..tamanho {
+        caminho.push(v);
+        v = prox[v];
+    }
+    caminho
+}
+
+// Aresta (a, prox[a]) do tour onde `v` é inserido com menor acréscimo
+fn melhor_posicao<G: Instancia>(grafo: &G,
+                                prox: &[Vertice],
+                                inicio: Vertice,
+                                tamanho: usize,
+                                v: Vertice)
+                                -> (f64, Vertice) {
+    let mut melhor = (f64::INFINITY, inicio);
+    let mut a = inicio;
+    for _ in 0..tamanho {
+        let b = prox[a];
+        let acrescimo = d(grafo, a, v) + d(grafo, v, b) - d(grafo, a, b);
+        if acrescimo < melhor.0 {
+            melhor = (acrescimo, a);
+        }
+        a = b;
+    }
+    melhor
+}
+
+// Inserção mais próxima, mais distante ou aleatória: escolhe o vértice pelo
+// critério e o insere na posição mais barata
+fn insercao<R, G>(rng: &mut R, grafo: &G, criterio: Heuristica) -> Caminho
+    where R: Rng,
+          G: Instancia
+{
+    let n = grafo.num_vertices();
+    if n < 3 {
+        return (0..n).collect();
+    }
+
+    let inicial = if criterio == Heuristica::InsercaoAleatoria {
+        rng.gen::<Vertice>() % n
+    } else {
+        0
+    };
+    let mut no_tour = vec![false; n];
+    let mut prox = vec![0; n];
+    // Distância de cada vértice ao tour
+    let mut dist = (0..n)
+        .map(|v| d(grafo, inicial, v).min(d(grafo, v, inicial)))
+        .collect::<Vec<_>>();
+    no_tour[inicial] = true;
+    prox[inicial] = inicial;
+
+    for tamanho in 1..n {
+        let fora = (0..n).filter(|&v| !no_tour[v]);
+        let v = match criterio {
+            Heuristica::InsercaoMaisProxima => {
+                fora.min_by(|&a, &b| compara(dist[a], dist[b])).unwrap()
+            }
+            Heuristica::InsercaoMaisDistante => {
+                fora.max_by(|&a, &b| compara(dist[a], dist[b])).unwrap()
+            }
+            _ => {
+                let fora = fora.collect::<Vec<_>>();
+                fora[rng.gen::<usize>() % fora.len()]
+            }
+        };
+
+        let (_, a) = melhor_posicao(grafo, &prox, inicial, tamanho, v);
+        prox[v] = prox[a];
+        prox[a] = v;
+        no_tour[v] = true;
+        for u in 0..n {
+            dist[u] = dist[u].min(d(grafo, v, u)).min(d(grafo, u, v));
+        }
+    }
+
+    caminho_de_sucessores(&prox, inicial, n)
+}
+
+// Insere a cada passo o vértice de menor acréscimo. A melhor posição de cada
+// vértice só é recalculada por completo quando a aresta escolhida some do tour.
+fn insercao_mais_barata<G: Instancia>(grafo: &G) -> Caminho {
+    let n = grafo.num_vertices();
+    if n < 3 {
+        return (0..n).collect();
+    }
+
+    let mut no_tour = vec![false; n];
+    let mut prox = vec![0; n];
+    no_tour[0] = true;
+    prox[0] = 0;
+    let mut melhor = (0..n).map(|v| melhor_posicao(grafo, &prox, 0, 1, v)).collect::<Vec<_>>();
+
+    for tamanho in 1..n {
+        let v = (0..n)
+            .filter(|&v| !no_tour[v])
+            .min_by(|&a, &b| compara(melhor[a].0, melhor[b].0))
+            .unwrap();
+        let a = melhor[v].1;
+        let b = prox[a];
+        prox[v] = b;
+        prox[a] = v;
+        no_tour[v] = true;
+
+        for u in (0..n).filter(|&u| !no_tour[u]) {
+            if melhor[u].1 == a {
+                melhor[u] = melhor_posicao(grafo, &prox, 0, tamanho + 1, u);
+                continue;
+            }
+            for &(x, y) in &[(a, v), (v, b)] {
+                let acrescimo = d(grafo, x, u) + d(grafo, u, y) - d(grafo, x, y);
+                if acrescimo < melhor[u].0 {
+                    melhor[u] = (acrescimo, x);
+                }
+            }
+        }
+    }
+
+    caminho_de_sucessores(&prox, 0, n)
+}
+
+fn raiz(pai: &mut [Vertice], v: Vertice) -> Vertice {
+    let mut r = v;
+    while pai[r] != r {
+        r = pai[r];
+    }
+    let mut v = v;
+    while pai[v] != r {
+        let proximo = pai[v];
+        pai[v] = r;
+        v = proximo;
+    }
+    r
+}
+
+// Monta o tour a partir de um caminho hamiltoniano dado pelas listas de vizinhos
+fn caminho_de_arestas(vizinhos: &[Vec<Vertice>]) -> Caminho {
+    let n = vizinhos.len();
+    let inicio = (0..n).find(|&v| vizinhos[v].len() < 2).unwrap_or(0);
+    let mut caminho = Vec::with_capacity(n);
+    let (mut anterior, mut atual) = (inicio, inicio);
+    loop {
+        caminho.push(atual);
+        match vizinhos[atual].iter().find(|&&w| w != anterior && w != inicio) {
+            Some(&w) if caminho.len() < n => {
+                anterior = atual;
+                atual = w;
+            }
+            _ => return caminho,
+        }
+    }
+}
+
+// Junta arestas em ordem de prioridade, mantendo grau <= 2 e sem ciclos, até
+// formar um caminho hamiltoniano
+fn junta_arestas(n: usize, arestas: &[(Vertice, Vertice)]) -> Vec<Vec<Vertice>> {
+    let mut vizinhos = vec![Vec::with_capacity(2); n];
+    let mut pai = (0..n).collect::<Vec<_>>();
+    let mut num_arestas = 0;
+    for &(i, j) in arestas {
+        if num_arestas + 1 == n {
+            break;
+        }
+        if vizinhos[i].len() < 2 && vizinhos[j].len() < 2 {
+            let (ri, rj) = (raiz(&mut pai, i), raiz(&mut pai, j));
+            if ri != rj {
+                pai[ri] = rj;
+                vizinhos[i].push(j);
+                vizinhos[j].push(i);
+                num_arestas += 1;
+            }
+        }
+    }
+    vizinhos
+}
+
+// Clarke-Wright: parte de rotas hub-i-hub e as une pela maior economia
+// d(i, hub) + d(hub, j) - d(i, j)
+fn economias<G: Instancia>(grafo: &G, hub: Vertice) -> Caminho {
+    let n = grafo.num_vertices();
+    if n < 3 {
+        return (0..n).collect();
+    }
+
+    let outros = (0..n).filter(|&v| v != hub).collect::<Vec<_>>();
+    let mut pares = Vec::with_capacity(outros.len() * outros.len() / 2);
+    for (k, &i) in outros.iter().enumerate() {
+        for &j in &outros[k + 1..] {
+            let economia = d(grafo, i, hub) + d(grafo, hub, j) - d(grafo, i, j);
+            pares.push((economia, i, j));
+        }
+    }
+    pares.sort_by(|a, b| compara(b.0, a.0));
+
+    // Índices locais, sem o hub
+    let mut indice = vec![0; n];
+    for (k, &v) in outros.iter().enumerate() {
+        indice[v] = k;
+    }
+    let arestas = pares.iter().map(|&(_, i, j)| (indice[i], indice[j])).collect::<Vec<_>>();
+    let caminho = caminho_de_arestas(&junta_arestas(outros.len(), &arestas));
+
+    let mut tour = vec![hub];
+    tour.extend(caminho.into_iter().map(|k| outros[k]));
+    tour
+}
+
+// Aresta gulosa: acrescenta as arestas mais baratas que não criam vértice de
+// grau 3 nem ciclo antes do fim
+fn aresta_gulosa<G: Instancia>(grafo: &G) -> Caminho {
+    let n = grafo.num_vertices();
+    if n < 3 {
+        return (0..n).collect();
+    }
+
+    let mut arestas = Vec::with_capacity(n * (n - 1) / 2);
+    for i in 0..n {
+        for j in i + 1..n {
+            arestas.push((d(grafo, i, j).min(d(grafo, j, i)), i, j));
+        }
+    }
+    arestas.sort_by(|a, b| compara(a.0, b.0));
+    let arestas = arestas.into_iter().map(|(_, i, j)| (i, j)).collect::<Vec<_>>();
+    caminho_de_arestas(&junta_arestas(n, &arestas))
+}
+
+// Posição do ponto (x, y) na curva de Hilbert que cobre uma grade lado × lado
+fn indice_hilbert(lado: u64, mut x: u64, mut y: u64) -> u64 {
+    let mut indice = 0;
+    let mut s = lado / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        indice += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = lado - 1 - x;
+                y = lado - 1 - y;
+            }
+            let t = x;
+            x = y;
+            y = t;
+        }
+        s /= 2;
+    }
+    indice
+}
+
+// Visita os pontos na ordem da curva de Hilbert; só para instâncias com coordenadas
+fn curva_hilbert<G: Instancia>(grafo: &G) -> Result<Caminho, ErroConstrucao> {
+    let coordenadas = match grafo.coordenadas() {
+        Some(c) => c,
+        None => {
+            return Err(ErroConstrucao::NaoSuportada("space-filling curve needs node coordinates"
+                .to_string()))
+        }
+    };
+    let pontos = coordenadas.pontos();
+    let lado = 1 << 16;
+    let (min_x, max_x, min_y, max_y) = pontos.iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY),
+              |(x0, x1, y0, y1), p| (x0.min(p.x), x1.max(p.x), y0.min(p.y), y1.max(p.y)));
+    let escala = (max_x - min_x).max(max_y - min_y).max(f64::EPSILON);
+    let grade = |c: f64, min: f64| ((c - min) / escala * (lado - 1) as f64) as u64;
+
+    let mut caminho = (0..pontos.len()).collect::<Vec<_>>();
+    caminho.sort_by_key(|&i| {
+        indice_hilbert(lado, grade(pontos[i].x, min_x), grade(pontos[i].y, min_y))
+    });
+    Ok(caminho)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::rand;
+    use grafo::{Grafo, Solucao, INF};
+    use testes::{OTIMO_ATT48, confere_permutacao, confere_qualidade};
+    use erro::ErroConstrucao;
+    use grasp::Grasp;
+    use ag::Ag;
+
+    // Prisma: dois ciclos de tamanho k ligados por arestas entre vértices
+    // correspondentes. Só poucos caminhos aleatórios fecham um ciclo hamiltoniano.
+    fn prisma(k: usize) -> Grafo {
+        let n = 2 * k;
+        let mut m = vec![vec![INF; n]; n];
+        for i in 0..k {
+            let j = (i + 1) % k;
+            for &(a, b) in &[(i, j), (k + i, k + j), (i, k + i)] {
+                m[a][b] = 1;
+                m[b][a] = 1;
+            }
+        }
+        for (i, linha) in m.iter_mut().enumerate() {
+            linha[i] = 0;
+        }
+        Grafo::from_matriz(m)
+    }
+
+    #[test]
+    fn dfs_encontra_ciclo() {
+        let g = prisma(15);
+        let c = dfs_backtracking(&mut rand::weak_rng(), &g, LIMITE_NOS_DFS).unwrap();
+        assert_eq!(Solucao::new(&g, c).fo(), 30);
+    }
+
+    // Estrela: o centro é ponto de articulação
+    fn estrela() -> Grafo {
+        let mut m = vec![vec![INF; 4]; 4];
+        for i in 0..4 {
+            m[i][i] = 0;
+            if i > 0 {
+                m[0][i] = 1;
+                m[i][0] = 1;
+            }
+        }
+        Grafo::from_matriz(m)
+    }
+
+    #[test]
+    fn dfs_sem_ciclo() {
+        assert!(dfs_backtracking(&mut rand::weak_rng(), &estrela(), LIMITE_NOS_DFS).is_none());
+    }
+
+    #[test]
+    fn solvers_infactivel() {
+        let g = estrela();
+        match Grasp::new(&g).solve() {
+            Err(ErroConstrucao::Infactivel(_)) => {}
+            _ => panic!("esperava instância infactível"),
+        }
+        match Ag::new(&g).solve() {
+            Err(ErroConstrucao::Infactivel(_)) => {}
+            _ => panic!("esperava instância infactível"),
+        }
+    }
+
+    #[test]
+    fn solvers_grafo_esparso() {
+        let g = prisma(10);
+        let (s, _) = Grasp::new(&g).max_iter(2).num_vizinhos(1).solve().unwrap();
+        assert_eq!(s.fo(), 20);
+        let (s, _) = Ag::new(&g).max_iter(2).pop_tam(10).solve().unwrap();
+        assert!(s.fo() >= 20);
+    }
+
+    const TODAS: [Heuristica; 9] = [Heuristica::VizinhoMaisProximo,
+                                    Heuristica::InsercaoMaisProxima,
+                                    Heuristica::InsercaoMaisDistante,
+                                    Heuristica::InsercaoMaisBarata,
+                                    Heuristica::InsercaoAleatoria,
+                                    Heuristica::Economias,
+                                    Heuristica::ArestaGulosa,
+                                    Heuristica::CurvaHilbert,
+                                    Heuristica::Christofides];
+
+    #[test]
+    fn heuristicas_factiveis() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        for &h in &TODAS {
+            let s = constroi(&mut rand::weak_rng(), &g, h).unwrap();
+            confere_permutacao(s.caminho(), 48);
+            // Nenhuma construção deve ser muito pior que o ótimo
+            confere_qualidade(&g, &s, OTIMO_ATT48, 100);
+        }
+    }
+
+    #[test]
+    fn heuristicas_em_matriz() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        for &h in TODAS.iter().filter(|&&h| h != Heuristica::CurvaHilbert) {
+            let s = constroi(&mut rand::weak_rng(), &g, h).unwrap();
+            assert_eq!(s.caminho().len(), 15);
+            assert!(s.fo() >= 291);
+        }
+        match constroi(&mut rand::weak_rng(), &g, Heuristica::CurvaHilbert) {
+            Err(ErroConstrucao::NaoSuportada(_)) => {}
+            _ => panic!("esperava construção não suportada"),
+        }
+    }
+
+    #[test]
+    fn nomes() {
+        assert_eq!(Heuristica::from_nome("economias"), Some(Heuristica::Economias));
+        assert_eq!(Heuristica::from_nome("hilbert"), Some(Heuristica::CurvaHilbert));
+        assert_eq!(Heuristica::from_nome("christofides"), Some(Heuristica::Christofides));
+        assert_eq!(Heuristica::from_nome("2-opt"), None);
+    }
+
+    #[test]
+    fn sementes_dos_solvers() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let c = constroi(&mut rand::weak_rng(), &g, Heuristica::InsercaoMaisBarata).unwrap();
+        let (s, _) = Grasp::new(&g).max_iter(1).semente(c.caminho().clone()).solve().unwrap();
+        assert!(s.fo() <= c.fo());
+        let (s, _) = Ag::new(&g)
+            .max_iter(1)
+            .pop_tam(10)
+            .semente(c.caminho().clone())
+            .solve()
+            .unwrap();
+        assert!(s.fo() <= c.fo());
+    }
+}
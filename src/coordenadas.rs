@@ -0,0 +1,152 @@
+use grafo::Peso;
+
+// Valor de PI usado pela TSPLIB no cálculo das distâncias GEO
+const PI: f64 = 3.141592;
+const RAIO_TERRA: f64 = 6378.388;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ponto {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+// Funções de distância da TSPLIB, com as mesmas regras de arredondamento
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metrica {
+    Euc2d,
+    Euc3d,
+    Ceil2d,
+    Man2d,
+    Max2d,
+    Att,
+    Geo,
+}
+
+fn nint(x: f64) -> Peso {
+    (x + 0.5) as Peso
+}
+
+fn radianos(x: f64) -> f64 {
+    let graus = x.trunc();
+    let minutos = x - graus;
+    PI * (graus + 5.0 * minutos / 3.0) / 180.0
+}
+
+impl Metrica {
+    pub fn distancia(&self, a: &Ponto, b: &Ponto) -> Peso {
+        let dx = a.x - b.x;
+        let dy = a.y - b.y;
+        let dz = a.z - b.z;
+        match *self {
+            Metrica::Euc2d => nint((dx * dx + dy * dy).sqrt()),
+            Metrica::Euc3d => nint((dx * dx + dy * dy + dz * dz).sqrt()),
+            Metrica::Ceil2d => (dx * dx + dy * dy).sqrt().ceil() as Peso,
+            Metrica::Man2d => nint(dx.abs() + dy.abs()),
+            Metrica::Max2d => {
+                let px = nint(dx.abs());
+                let py = nint(dy.abs());
+                if px > py { px } else { py }
+            }
+            Metrica::Att => {
+                let r = ((dx * dx + dy * dy) / 10.0).sqrt();
+                let t = nint(r);
+                if (t as f64) < r { t + 1 } else { t }
+            }
+            Metrica::Geo => {
+                let (lat_a, lon_a) = (radianos(a.x), radianos(a.y));
+                let (lat_b, lon_b) = (radianos(b.x), radianos(b.y));
+                let q1 = (lon_a - lon_b).cos();
+                let q2 = (lat_a - lat_b).cos();
+                let q3 = (lat_a + lat_b).cos();
+                (RAIO_TERRA * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0) as Peso
+            }
+        }
+    }
+
+    // Mesma distância, sem o arredondamento para inteiro
+    pub fn distancia_real(&self, a: &Ponto, b: &Ponto) -> f64 {
+        let dx = a.x - b.x;
+        let dy = a.y - b.y;
+        let dz = a.z - b.z;
+        match *self {
+            Metrica::Euc2d | Metrica::Ceil2d => (dx * dx + dy * dy).sqrt(),
+            Metrica::Euc3d => (dx * dx + dy * dy + dz * dz).sqrt(),
+            Metrica::Man2d => dx.abs() + dy.abs(),
+            Metrica::Max2d => dx.abs().max(dy.abs()),
+            Metrica::Att => ((dx * dx + dy * dy) / 10.0).sqrt(),
+            Metrica::Geo => {
+                let (lat_a, lon_a) = (radianos(a.x), radianos(a.y));
+                let (lat_b, lon_b) = (radianos(b.x), radianos(b.y));
+                let q1 = (lon_a - lon_b).cos();
+                let q2 = (lat_a - lat_b).cos();
+                let q3 = (lat_a + lat_b).cos();
+                RAIO_TERRA * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos()
+            }
+        }
+    }
+}
+
+// Instância definida por pontos: as distâncias são calculadas sob demanda,
+// sem guardar a matriz n×n
+#[derive(Clone, Debug)]
+pub struct Coordenadas {
+    pontos: Vec<Ponto>,
+    metrica: Metrica,
+}
+
+impl Coordenadas {
+    pub fn new(pontos: Vec<Ponto>, metrica: Metrica) -> Coordenadas {
+        Coordenadas {
+            pontos: pontos,
+            metrica: metrica,
+        }
+    }
+
+    pub fn pontos(&self) -> &[Ponto] {
+        &self.pontos
+    }
+
+    pub fn metrica(&self) -> Metrica {
+        self.metrica
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Solucao};
+    use instancia::Instancia;
+
+    #[test]
+    fn sob_demanda_igual_matriz() {
+        let densa: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let lazy: Grafo = Grafo::from_coordenadas(densa.coordenadas().unwrap().clone());
+        let real = Grafo::<f64>::from_coordenadas(densa.coordenadas().unwrap().clone());
+        for i in 0..48 {
+            for j in 0..48 {
+                assert_eq!(lazy.distancia(i, j), densa.distancia(i, j));
+                // ATT arredonda para cima
+                let d = densa.distancia(i, j) as f64 - real.distancia(i, j);
+                assert!(d >= 0.0 && d < 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn instancia_grande_sem_matriz() {
+        let n = 50000;
+        let pontos = (0..n)
+            .map(|i| {
+                Ponto {
+                    x: i as f64,
+                    y: 0.0,
+                    z: 0.0,
+                }
+            })
+            .collect();
+        let g: Grafo = Grafo::from_coordenadas(Coordenadas::new(pontos, Metrica::Euc2d));
+        let s = Solucao::new(&g, (0..n).collect());
+        assert_eq!(s.fo(), 2 * (n as u64 - 1));
+    }
+}
@@ -0,0 +1,140 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+use std::iter::Sum;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use coordenadas::{Metrica, Ponto};
+
+// Tolerância relativa usada nas comparações de pesos reais
+const EPSILON: f64 = 1e-9;
+
+// Tipo numérico dos pesos das arestas. Inteiros são comparados de forma exata e
+// reais com tolerância, de modo que erros de arredondamento não contem como
+// melhora.
+pub trait Custo
+    : Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Sum + FromStr + Debug + Display
+    + Send + Sync + 'static {
+    fn zero() -> Self;
+
+    // Peso de uma aresta ausente
+    fn inf() -> Self;
+
+    fn to_f64(self) -> f64;
+
+    #[allow(dead_code)]
+    fn from_f64(x: f64) -> Self;
+
+    // Distância entre dois pontos segundo a métrica. Inteiros seguem o
+    // arredondamento da TSPLIB; reais usam o valor exato.
+    fn da_metrica(metrica: &Metrica, a: &Ponto, b: &Ponto) -> Self;
+
+    // Menor custo que um limite inferior real `x` permite. Como um tour inteiro
+    // custa um valor inteiro, inteiros arredondam para cima.
+    fn limite_inferior(x: f64) -> Self;
+
+    fn menor(self, outro: Self) -> bool;
+
+    fn igual(self, outro: Self) -> bool {
+        !self.menor(outro) && !outro.menor(self)
+    }
+
+    fn is_inf(self) -> bool {
+        !self.menor(Self::inf())
+    }
+
+    fn compara(self, outro: Self) -> Ordering {
+        if self.menor(outro) {
+            Ordering::Less
+        } else if outro.menor(self) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+impl Custo for u64 {
+    fn zero() -> u64 {
+        0
+    }
+
+    fn inf() -> u64 {
+        ::grafo::INF
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(x: f64) -> u64 {
+        x.round() as u64
+    }
+
+    fn da_metrica(metrica: &Metrica, a: &Ponto, b: &Ponto) -> u64 {
+        metrica.distancia(a, b)
+    }
+
+    fn limite_inferior(x: f64) -> u64 {
+        (x - EPSILON * x.abs().max(1.0)).ceil().max(0.0) as u64
+    }
+
+    fn menor(self, outro: u64) -> bool {
+        self < outro
+    }
+}
+
+impl Custo for f64 {
+    fn zero() -> f64 {
+        0.0
+    }
+
+    fn inf() -> f64 {
+        ::grafo::INF as f64
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(x: f64) -> f64 {
+        x
+    }
+
+    fn da_metrica(metrica: &Metrica, a: &Ponto, b: &Ponto) -> f64 {
+        metrica.distancia_real(a, b)
+    }
+
+    fn limite_inferior(x: f64) -> f64 {
+        x
+    }
+
+    fn menor(self, outro: f64) -> bool {
+        self < outro - EPSILON * outro.abs().max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn tolerancia_real() {
+        assert!(!(0.1 + 0.2).menor(0.3));
+        assert!((0.1 + 0.2).igual(0.3));
+        assert!(0.3.menor(0.3001));
+        assert_eq!(2.0.compara(1.0), Ordering::Greater);
+        assert!(f64::inf().is_inf());
+        assert!(!1e6.is_inf());
+    }
+
+    #[test]
+    fn inteiros_exatos() {
+        assert!(2u64.menor(3));
+        assert!(!3u64.menor(3));
+        assert!(u64::inf().is_inf());
+        assert_eq!(u64::limite_inferior(10627.999999999), 10628);
+        assert_eq!(u64::limite_inferior(10628.000000001), 10628);
+        assert_eq!(u64::limite_inferior(10628.3), 10629);
+    }
+}
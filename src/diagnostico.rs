@@ -0,0 +1,411 @@
+use std::fmt;
+use grafo::{Grafo, Peso, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use erro::ErroConstrucao;
+
+// Acima desse tamanho a verificação O(n³) da desigualdade triangular é pulada
+const LIMITE_TRIANGULAR: usize = 500;
+const NUM_PIORES_VIOLACOES: usize = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violacao<P = Peso> {
+    pub i: Vertice,
+    pub j: Vertice,
+    pub k: Vertice,
+    pub excesso: P,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostico<P = Peso> {
+    pub num_vertices: usize,
+    pub quadrada: bool,
+    pub diagonal_zero: bool,
+    pub simetrica: bool,
+    pub pares_assimetricos: usize,
+    pub desigualdade_triangular: Option<bool>,
+    pub num_violacoes: usize,
+    pub piores_violacoes: Vec<Violacao<P>>,
+    pub arestas_inf: usize,
+    pub fortemente_conexo: bool,
+    pub grau_minimo: usize,
+    pub articulacoes: Vec<Vertice>,
+}
+
+impl<P> Diagnostico<P> {
+    // Condições necessárias (não suficientes) para existir um ciclo hamiltoniano
+    // usando apenas arestas finitas
+    pub fn hamiltoniano_possivel(&self) -> bool {
+        match self.num_vertices {
+            0 => false,
+            1 => self.quadrada,
+            2 => self.quadrada && self.fortemente_conexo,
+            _ => {
+                self.quadrada && self.fortemente_conexo && self.grau_minimo >= 2 &&
+                self.articulacoes.is_empty()
+            }
+        }
+    }
+
+    pub fn metrica(&self) -> bool {
+        self.quadrada && self.diagonal_zero && self.simetrica &&
+        self.desigualdade_triangular == Some(true) && self.arestas_inf == 0
+    }
+}
+
+fn alcancaveis<G: Instancia>(grafo: &G, origem: Vertice, reverso: bool) -> Vec<bool> {
+    let n = grafo.num_vertices();
+    let mut marcados = vec![false; n];
+    let mut pilha = vec![origem];
+    marcados[origem] = true;
+
+    while let Some(i) = pilha.pop() {
+        for j in 0..n {
+            let existe = if reverso { grafo.aresta(j, i) } else { grafo.aresta(i, j) };
+            if existe && !marcados[j] {
+                marcados[j] = true;
+                pilha.push(j);
+            }
+        }
+    }
+
+    marcados
+}
+
+fn vizinhos_nao_direcionados<G: Instancia>(grafo: &G) -> Vec<Vec<Vertice>> {
+    let n = grafo.num_vertices();
+    (0..n)
+        .map(|i| (0..n).filter(|&j| grafo.aresta(i, j) || grafo.aresta(j, i)).collect())
+        .collect()
+}
+
+// Pontos de articulação (Tarjan), em versão iterativa para não estourar a pilha
+fn articulacoes(vizinhos: &[Vec<Vertice>]) -> Vec<Vertice> {
+    let n = vizinhos.len();
+    let mut descoberta = vec![0; n];
+    let mut low = vec![0; n];
+    let mut visitado = vec![false; n];
+    let mut articulacao = vec![false; n];
+    let mut tempo = 0;
+
+    for raiz in 0..n {
+        if visitado[raiz] {
+            continue;
+        }
+        visitado[raiz] = true;
+        tempo += 1;
+        descoberta[raiz] = tempo;
+        low[raiz] = tempo;
+        let mut filhos_raiz = 0;
+        // (vértice, pai, próximo índice de vizinho a visitar)
+        let mut pilha = vec![(raiz, raiz, 0)];
+
+        while let Some(&(v, pai, prox)) = pilha.last() {
+            if prox < vizinhos[v].len() {
+                let w = vizinhos[v][prox];
+                let topo = pilha.len() - 1;
+                pilha[topo].2 += 1;
+                if !visitado[w] {
+                    visitado[w] = true;
+                    tempo += 1;
+                    descoberta[w] = tempo;
+                    low[w] = tempo;
+                    if v == raiz {
+                        filhos_raiz += 1;
+                    }
+                    pilha.push((w, v, 0));
+                } else if w != pai {
+                    low[v] = low[v].min(descoberta[w]);
+                }
+            } else {
+                pilha.pop();
+                if v != raiz {
+                    low[pai] = low[pai].min(low[v]);
+                    if pai != raiz && low[v] >= descoberta[pai] {
+                        articulacao[pai] = true;
+                    }
+                }
+            }
+        }
+
+        if filhos_raiz > 1 {
+            articulacao[raiz] = true;
+        }
+    }
+
+    (0..n).filter(|&v| articulacao[v]).collect()
+}
+
+fn verifica_triangular<G: Instancia>(grafo: &G) -> (usize, Vec<Violacao<G::Peso>>) {
+    let n = grafo.num_vertices();
+    let mut num_violacoes = 0;
+    let mut piores: Vec<Violacao<G::Peso>> = Vec::with_capacity(NUM_PIORES_VIOLACOES + 1);
+
+    for i in 0..n {
+        for k in 0..n {
+            if !grafo.aresta(i, k) {
+                continue;
+            }
+            let direto = grafo.distancia(i, k);
+            for j in 0..n {
+                if j == i || j == k || !grafo.aresta(i, j) || !grafo.aresta(j, k) {
+                    continue;
+                }
+                let soma = grafo.distancia(i, j) + grafo.distancia(j, k);
+                if !soma.menor(direto) {
+                    continue;
+                }
+
+                num_violacoes += 1;
+                let excesso = direto - soma;
+                if piores.len() < NUM_PIORES_VIOLACOES ||
+                   piores[piores.len() - 1].excesso.menor(excesso) {
+                    let pos = piores.iter()
+                        .position(|v| v.excesso.menor(excesso))
+                        .unwrap_or(piores.len());
+                    piores.insert(pos,
+                                  Violacao {
+                                      i: i,
+                                      j: j,
+                                      k: k,
+                                      excesso: excesso,
+                                  });
+                    piores.truncate(NUM_PIORES_VIOLACOES);
+                }
+            }
+        }
+    }
+
+    (num_violacoes, piores)
+}
+
+impl<P: Custo> Grafo<P> {
+    pub fn diagnostico(&self) -> Diagnostico<P> {
+        if self.is_quadrada() {
+            analisa(self)
+        } else {
+            Diagnostico { quadrada: false, ..vazio(self.num_vertices()) }
+        }
+    }
+}
+
+fn vazio<P>(n: usize) -> Diagnostico<P> {
+    Diagnostico {
+        num_vertices: n,
+        quadrada: true,
+        diagonal_zero: false,
+        simetrica: false,
+        pares_assimetricos: 0,
+        desigualdade_triangular: None,
+        num_violacoes: 0,
+        piores_violacoes: vec![],
+        arestas_inf: 0,
+        fortemente_conexo: false,
+        grau_minimo: 0,
+        articulacoes: vec![],
+    }
+}
+
+pub fn analisa<G: Instancia>(grafo: &G) -> Diagnostico<G::Peso> {
+    let n = grafo.num_vertices();
+    let mut diag = vazio(n);
+    if n == 0 {
+        return diag;
+    }
+
+    if n <= LIMITE_TRIANGULAR {
+        let (num_violacoes, piores) = verifica_triangular(grafo);
+        diag.desigualdade_triangular = Some(num_violacoes == 0);
+        diag.num_violacoes = num_violacoes;
+        diag.piores_violacoes = piores;
+    }
+
+    // Distâncias calculadas por uma métrica: grafo completo e simétrico
+    if grafo.coordenadas().is_some() {
+        diag.diagonal_zero = true;
+        diag.simetrica = true;
+        diag.fortemente_conexo = true;
+        diag.grau_minimo = n - 1;
+        return diag;
+    }
+
+    diag.diagonal_zero = (0..n).all(|i| grafo.distancia(i, i).igual(Custo::zero()));
+    for i in 0..n {
+        for j in 0..n {
+            if i != j && !grafo.aresta(i, j) {
+                diag.arestas_inf += 1;
+            }
+            if i < j && !grafo.distancia(i, j).igual(grafo.distancia(j, i)) {
+                diag.pares_assimetricos += 1;
+            }
+        }
+    }
+    diag.simetrica = diag.pares_assimetricos == 0;
+
+    diag.fortemente_conexo = alcancaveis(grafo, 0, false).into_iter().all(|m| m) &&
+                             alcancaveis(grafo, 0, true).into_iter().all(|m| m);
+
+    let vizinhos = vizinhos_nao_direcionados(grafo);
+    diag.grau_minimo = vizinhos.iter().map(|v| v.len()).min().unwrap_or(0);
+    diag.articulacoes = articulacoes(&vizinhos);
+
+    diag
+}
+
+// Pesos simétricos, sem contar os pares assimétricos como `analisa`
+pub fn simetrica<G: Instancia>(grafo: &G) -> bool {
+    if grafo.coordenadas().is_some() {
+        return true;
+    }
+    let n = grafo.num_vertices();
+    (0..n).all(|i| (i + 1..n).all(|j| grafo.distancia(i, j).igual(grafo.distancia(j, i))))
+}
+
+// Versão barata (O(n²)) das condições de `hamiltoniano_possivel`, usada pelos
+// solvers antes de construir soluções
+pub fn verifica_hamiltoniano<G: Instancia>(grafo: &G) -> Result<(), ErroConstrucao> {
+    let n = grafo.num_vertices();
+    if n == 0 {
+        return Err(ErroConstrucao::Infactivel("graph has no vertices".to_string()));
+    }
+    if grafo.coordenadas().is_some() {
+        return Ok(());
+    }
+    if !alcancaveis(grafo, 0, false).into_iter().all(|m| m) ||
+       !alcancaveis(grafo, 0, true).into_iter().all(|m| m) {
+        return Err(ErroConstrucao::Infactivel("graph of finite edges is not strongly connected"
+            .to_string()));
+    }
+    if n < 3 {
+        return Ok(());
+    }
+
+    let vizinhos = vizinhos_nao_direcionados(grafo);
+    if let Some(v) = (0..n).find(|&v| vizinhos[v].len() < 2) {
+        return Err(ErroConstrucao::Infactivel(format!("vertex {} has fewer than two finite \
+                                                        edges",
+                                                       v)));
+    }
+    if let Some(&v) = articulacoes(&vizinhos).first() {
+        return Err(ErroConstrucao::Infactivel(format!("vertex {} is an articulation point", v)));
+    }
+
+    Ok(())
+}
+
+fn sim_nao(b: bool) -> &'static str {
+    if b { "sim" } else { "não" }
+}
+
+impl<P: fmt::Display> fmt::Display for Diagnostico<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Vértices: {}", self.num_vertices)?;
+        writeln!(f, "Matriz quadrada: {}", sim_nao(self.quadrada))?;
+        if !self.quadrada {
+            return writeln!(f, "Demais verificações ignoradas");
+        }
+
+        writeln!(f, "Diagonal zero: {}", sim_nao(self.diagonal_zero))?;
+        if self.simetrica {
+            writeln!(f, "Simétrica: sim")?;
+        } else {
+            writeln!(f,
+                     "Simétrica: não ({} pares assimétricos)",
+                     self.pares_assimetricos)?;
+        }
+
+        match self.desigualdade_triangular {
+            None => {
+                writeln!(f,
+                         "Desigualdade triangular: não verificada (mais de {} vértices)",
+                         LIMITE_TRIANGULAR)?
+            }
+            Some(true) => writeln!(f, "Desigualdade triangular: respeitada")?,
+            Some(false) => {
+                writeln!(f,
+                         "Desigualdade triangular: violada em {} triplas",
+                         self.num_violacoes)?;
+                for v in &self.piores_violacoes {
+                    writeln!(f,
+                             "    d({}, {}) excede d({}, {}) + d({}, {}) em {}",
+                             v.i,
+                             v.k,
+                             v.i,
+                             v.j,
+                             v.j,
+                             v.k,
+                             v.excesso)?;
+                }
+            }
+        }
+
+        writeln!(f, "Arestas ausentes (INF): {}", self.arestas_inf)?;
+        writeln!(f, "Fortemente conexo: {}", sim_nao(self.fortemente_conexo))?;
+        writeln!(f, "Grau mínimo: {}", self.grau_minimo)?;
+        if self.articulacoes.is_empty() {
+            writeln!(f, "Pontos de articulação: nenhum")?;
+        } else {
+            writeln!(f, "Pontos de articulação: {:?}", self.articulacoes)?;
+        }
+        writeln!(f,
+                 "Ciclo hamiltoniano: {}",
+                 if self.hamiltoniano_possivel() { "possível" } else { "impossível" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Peso, INF};
+
+    #[test]
+    fn toy_viola_desigualdade() {
+        let d = Grafo::toy().diagnostico();
+        assert!(d.quadrada && d.diagonal_zero && d.simetrica);
+        assert_eq!(d.desigualdade_triangular, Some(false));
+        assert_eq!(d.piores_violacoes[0],
+                   Violacao {
+                       i: 1,
+                       j: 0,
+                       k: 3,
+                       excesso: 2,
+                   });
+        assert!(d.hamiltoniano_possivel());
+        assert!(!d.metrica());
+    }
+
+    #[test]
+    fn p01_metrica() {
+        let d = Grafo::<Peso>::from_arquivo("instances/p01.txt").unwrap().diagnostico();
+        assert!(d.metrica());
+        assert!(d.hamiltoniano_possivel());
+
+        let d = Grafo::<Peso>::from_tsplib("instances/gr17.tsp").unwrap().diagnostico();
+        assert!(d.simetrica);
+        assert_eq!(d.num_violacoes, 134);
+    }
+
+    #[test]
+    fn articulacao_impede_ciclo() {
+        // Dois triângulos ligados apenas pelo vértice 2
+        let g = Grafo::from_matriz(vec![vec![0, 1, 1, INF, INF],
+                                        vec![1, 0, 1, INF, INF],
+                                        vec![1, 1, 0, 1, 1],
+                                        vec![INF, INF, 1, 0, 1],
+                                        vec![INF, INF, 1, 1, 0]]);
+        let d = g.diagnostico();
+        assert!(d.fortemente_conexo);
+        assert_eq!(d.grau_minimo, 2);
+        assert_eq!(d.articulacoes, vec![2]);
+        assert_eq!(d.arestas_inf, 8);
+        assert!(!d.hamiltoniano_possivel());
+    }
+
+    #[test]
+    fn desconexo() {
+        let g = Grafo::from_matriz(vec![vec![0, 1, INF], vec![1, 0, INF], vec![INF, INF, 0]]);
+        let d = g.diagnostico();
+        assert!(!d.fortemente_conexo);
+        assert!(!d.hamiltoniano_possivel());
+    }
+}
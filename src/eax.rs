@@ -0,0 +1,364 @@
+extern crate rand;
+
+use std::usize;
+use self::rand::Rng;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use candidatos::Candidatos;
+
+// Filhos gerados a partir de uma decomposição em ciclos AB, cada um com um
+// ciclo central diferente; o cruzamento devolve o melhor
+const FILHOS_POR_CRUZAMENTO: usize = 10;
+
+// Vizinho ausente na lista de adjacência do tour intermediário
+const NENHUM: Vertice = usize::MAX;
+
+// Como os ciclos AB são escolhidos para formar o E-set
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Estrategia {
+    // Um único ciclo AB sorteado (EAX-1AB)
+    Unica,
+    // Um ciclo AB central mais, com probabilidade 1/2, cada ciclo AB que
+    // compartilha vértices com ele
+    Bloco,
+}
+
+// Ciclo AB: a aresta (c[i], c[i + 1]) é de A nas posições pares e de B nas
+// ímpares, e a última volta a c[0]
+type CicloAb = Vec<Vertice>;
+
+// Os dois vizinhos de cada vértice no tour
+fn adjacencias(caminho: &Caminho) -> Vec<[Vertice; 2]> {
+    let n = caminho.len();
+    let mut adj = vec![[0; 2]; n];
+    for i in 0..n {
+        adj[caminho[i]] = [caminho[(i + n - 1) % n], caminho[(i + 1) % n]];
+    }
+    adj
+}
+
+fn remove(lista: &mut Vec<Vertice>, v: Vertice) {
+    let i = lista.iter().position(|&u| u == v).unwrap();
+    lista.swap_remove(i);
+}
+
+// Decompõe as arestas exclusivas de A ou de B em ciclos AB. O passeio sai dos
+// vértices em posição par por arestas de A e dos em posição ímpar por arestas
+// de B; quando chega a um vértice já visitado numa posição de mesma paridade,
+// o trecho entre as duas visitas é um ciclo AB.
+fn ciclos_ab<R: Rng>(rng: &mut R, a: &Caminho, b: &Caminho) -> Vec<CicloAb> {
+    let n = a.len();
+    let (adj_a, adj_b) = (adjacencias(a), adjacencias(b));
+    let exclusivas = |adj: &[[Vertice; 2]], outra: &[[Vertice; 2]]| {
+        (0..n)
+            .map(|v| adj[v].iter().cloned().filter(|u| !outra[v].contains(u)).collect())
+            .collect::<Vec<Vec<Vertice>>>()
+    };
+    let mut resto_a = exclusivas(&adj_a, &adj_b);
+    let mut resto_b = exclusivas(&adj_b, &adj_a);
+
+    let mut ciclos = vec![];
+    let mut passeio: Vec<Vertice> = vec![];
+    let mut posicoes = vec![vec![]; n];
+    let mut inicio = 0;
+    loop {
+        if passeio.is_empty() {
+            while inicio < n && resto_a[inicio].is_empty() {
+                inicio += 1;
+            }
+            if inicio == n {
+                break;
+            }
+            passeio.push(inicio);
+            posicoes[inicio].push(0);
+        }
+        let k = passeio.len();
+        let v = passeio[k - 1];
+        let u = {
+            let resto = if k % 2 == 1 { &mut resto_a } else { &mut resto_b };
+            // Só acontece com o passeio reduzido ao vértice inicial
+            if resto[v].is_empty() {
+                None
+            } else {
+                let u = resto[v][rng.gen_range(0, resto[v].len())];
+                remove(&mut resto[v], u);
+                remove(&mut resto[u], v);
+                Some(u)
+            }
+        };
+        let u = match u {
+            Some(u) => u,
+            None => {
+                passeio.clear();
+                posicoes[v].clear();
+                continue;
+            }
+        };
+
+        let fecha = posicoes[u].iter().rev().cloned().find(|&j| j % 2 == k % 2);
+        match fecha {
+            Some(j) => {
+                let mut ciclo = passeio[j..].to_vec();
+                // Se o ciclo começa por uma aresta de B, gira uma posição
+                if j % 2 == 1 {
+                    let primeiro = ciclo.remove(0);
+                    ciclo.push(primeiro);
+                }
+                ciclos.push(ciclo);
+                for &w in &passeio[j + 1..] {
+                    posicoes[w].pop();
+                }
+                passeio.truncate(j + 1);
+            }
+            None => {
+                posicoes[u].push(k);
+                passeio.push(u);
+            }
+        }
+    }
+    ciclos
+}
+
+// Ciclos do E-set para um ciclo central
+fn e_set<R: Rng>(rng: &mut R,
+                 ciclos: &[CicloAb],
+                 centro: usize,
+                 estrategia: Estrategia,
+                 n: usize)
+                 -> Vec<usize> {
+    match estrategia {
+        Estrategia::Unica => vec![centro],
+        Estrategia::Bloco => {
+            let mut no_centro = vec![false; n];
+            for &v in &ciclos[centro] {
+                no_centro[v] = true;
+            }
+            let mut escolhidos = vec![centro];
+            for (i, ciclo) in ciclos.iter().enumerate() {
+                if i != centro && ciclo.iter().any(|&v| no_centro[v]) && rng.gen() {
+                    escolhidos.push(i);
+                }
+            }
+            escolhidos
+        }
+    }
+}
+
+fn troca_vizinho(adj: &mut [[Vertice; 2]], v: Vertice, antigo: Vertice, novo: Vertice) {
+    let i = if adj[v][0] == antigo { 0 } else { 1 };
+    adj[v][i] = novo;
+}
+
+// Rótulo do subtour de cada vértice e os vértices de cada subtour, em ordem
+fn subtours(adj: &[[Vertice; 2]]) -> (Vec<usize>, Vec<Vec<Vertice>>) {
+    let n = adj.len();
+    let mut rotulo = vec![NENHUM; n];
+    let mut subtours = vec![];
+    for s in 0..n {
+        if rotulo[s] != NENHUM {
+            continue;
+        }
+        let mut subtour = vec![];
+        let (mut anterior, mut atual) = (NENHUM, s);
+        loop {
+            rotulo[atual] = subtours.len();
+            subtour.push(atual);
+            let proximo = if adj[atual][0] != anterior { adj[atual][0] } else { adj[atual][1] };
+            if proximo == s {
+                break;
+            }
+            anterior = atual;
+            atual = proximo;
+        }
+        subtours.push(subtour);
+    }
+    (rotulo, subtours)
+}
+
+// Junta os subtours do tour intermediário, sempre a partir do menor: troca
+// uma aresta (u1, u2) dele e uma aresta (v1, v2) de outro subtour pelas duas
+// arestas mais baratas que os ligam. v1 vem da lista de candidatos de u1 ou,
+// se nenhum candidato está fora do subtour, de todos os vértices.
+fn junta_subtours<G: Instancia>(grafo: &G, adj: &mut [[Vertice; 2]], candidatos: &Candidatos) {
+    let n = adj.len();
+    let d = |i, j| grafo.distancia(i, j);
+    loop {
+        let (rotulo, componentes) = subtours(adj);
+        if componentes.len() == 1 {
+            return;
+        }
+        let menor = componentes.iter().min_by_key(|s| s.len()).unwrap();
+        let mut melhor: Option<(G::Peso, G::Peso, (Vertice, Vertice, Vertice, Vertice))> = None;
+
+        for &completa in &[false, true] {
+            if melhor.is_some() {
+                break;
+            }
+            for (i, &u1) in menor.iter().enumerate() {
+                let u2 = menor[(i + 1) % menor.len()];
+                let tamanho = if completa { n } else { candidatos[u1].len() };
+                for t in 0..tamanho {
+                    let v1 = if completa { t } else { candidatos[u1][t] };
+                    if rotulo[v1] == rotulo[u1] {
+                        continue;
+                    }
+                    for &v2 in &adj[v1] {
+                        let removidas = d(u1, u2) + d(v1, v2);
+                        for &(x, y) in &[(v1, v2), (v2, v1)] {
+                            let adicionadas = d(u1, x) + d(u2, y);
+                            let troca = match melhor {
+                                None => true,
+                                Some((r, a, _)) => (adicionadas + r).menor(a + removidas),
+                            };
+                            if troca {
+                                melhor = Some((removidas, adicionadas, (u1, u2, x, y)));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // (u1, u2) e (x, y) saem; (u1, x) e (u2, y) entram
+        let (u1, u2, x, y) = melhor.unwrap().2;
+        troca_vizinho(adj, u1, u2, x);
+        troca_vizinho(adj, u2, u1, y);
+        troca_vizinho(adj, x, y, u1);
+        troca_vizinho(adj, y, x, u2);
+    }
+}
+
+fn percorre(adj: &[[Vertice; 2]]) -> Caminho {
+    let mut caminho = Vec::with_capacity(adj.len());
+    let (mut anterior, mut atual) = (adj[0][0], 0);
+    for _ in 0..adj.len() {
+        caminho.push(atual);
+        let proximo = if adj[atual][0] != anterior { adj[atual][0] } else { adj[atual][1] };
+        anterior = atual;
+        atual = proximo;
+    }
+    caminho
+}
+
+// Edge Assembly Crossover: parte do pai A, troca as arestas de A de um E-set
+// de ciclos AB pelas arestas de B e junta os subtours resultantes. Gera até
+// FILHOS_POR_CRUZAMENTO filhos e devolve o melhor. Supõe pesos simétricos.
+pub fn eax<R, G>(rng: &mut R,
+                 grafo: &G,
+                 a: &Caminho,
+                 b: &Caminho,
+                 estrategia: Estrategia,
+                 candidatos: &Candidatos)
+                 -> Caminho
+    where R: Rng,
+          G: Instancia
+{
+    let n = a.len();
+    let ciclos = ciclos_ab(rng, a, b);
+    if ciclos.is_empty() {
+        return a.clone();
+    }
+    let adj_a = adjacencias(a);
+    let mut centros = (0..ciclos.len()).collect::<Vec<_>>();
+    rng.shuffle(&mut centros);
+
+    let mut melhor: Option<Solucao<G::Peso>> = None;
+    for &centro in centros.iter().take(FILHOS_POR_CRUZAMENTO) {
+        let mut adj = adj_a.clone();
+        let escolhidos = e_set(rng, &ciclos, centro, estrategia, n);
+        // Primeiro saem as arestas de A, depois entram as de B
+        for &paridade in &[0, 1] {
+            for &c in &escolhidos {
+                let ciclo = &ciclos[c];
+                for i in (0..ciclo.len()).filter(|i| i % 2 == paridade) {
+                    let (u, v) = (ciclo[i], ciclo[(i + 1) % ciclo.len()]);
+                    let (antigo, novo) = if paridade == 0 { (v, NENHUM) } else { (NENHUM, v) };
+                    troca_vizinho(&mut adj, u, antigo, novo);
+                    let (antigo, novo) = if paridade == 0 { (u, NENHUM) } else { (NENHUM, u) };
+                    troca_vizinho(&mut adj, v, antigo, novo);
+                }
+            }
+        }
+        junta_subtours(grafo, &mut adj, candidatos);
+
+        let filho = Solucao::new(grafo, percorre(&adj));
+        if melhor.as_ref().map_or(true, |m| filho.fo().menor(m.fo())) {
+            melhor = Some(filho);
+        }
+    }
+    melhor.unwrap().caminho().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{confere_permutacao, tour_aleatorio};
+
+    fn tem_aresta(caminho: &Caminho, u: Vertice, v: Vertice) -> bool {
+        let adj = adjacencias(caminho);
+        adj[u].contains(&v)
+    }
+
+    #[test]
+    fn ciclos_ab_alternam() {
+        let mut rng = rand::weak_rng();
+        for n in 5..40 {
+            let (a, b) = (tour_aleatorio(n), tour_aleatorio(n));
+            let ciclos = ciclos_ab(&mut rng, &a, &b);
+            let mut arestas = 0;
+            for ciclo in &ciclos {
+                assert!(ciclo.len() % 2 == 0 && ciclo.len() >= 4);
+                for i in 0..ciclo.len() {
+                    let (u, v) = (ciclo[i], ciclo[(i + 1) % ciclo.len()]);
+                    let (de, fora) = if i % 2 == 0 { (&a, &b) } else { (&b, &a) };
+                    assert!(tem_aresta(de, u, v) && !tem_aresta(fora, u, v));
+                }
+                arestas += ciclo.len();
+            }
+            // Toda aresta exclusiva de um dos pais está em exatamente um ciclo
+            let exclusivas = (0..n)
+                .filter(|&i| !tem_aresta(&b, a[i], a[(i + 1) % n]))
+                .count();
+            assert_eq!(arestas, 2 * exclusivas);
+        }
+        let a = tour_aleatorio(20);
+        assert!(ciclos_ab(&mut rng, &a, &a).is_empty());
+    }
+
+    #[test]
+    fn filhos_validos() {
+        let mut rng = rand::weak_rng();
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(10);
+        for &estrategia in &[Estrategia::Unica, Estrategia::Bloco] {
+            for _ in 0..20 {
+                let (a, b) = (tour_aleatorio(48), tour_aleatorio(48));
+                let filho = eax(&mut rng, &g, &a, &b, estrategia, &candidatos);
+                confere_permutacao(&filho, 48);
+            }
+            // Pais iguais: o filho é o próprio pai
+            let a = tour_aleatorio(48);
+            assert_eq!(eax(&mut rng, &g, &a, &a, estrategia, &candidatos), a);
+        }
+    }
+
+    #[test]
+    fn junta_subtours_em_um_ciclo() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let candidatos = g.candidatos(3);
+        // Três triângulos e um ciclo de oito vértices
+        let mut adj = vec![[0; 2]; 17];
+        for ciclo in &[vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], (9..17).collect()] {
+            for i in 0..ciclo.len() {
+                let m = ciclo.len();
+                adj[ciclo[i]] = [ciclo[(i + m - 1) % m], ciclo[(i + 1) % m]];
+            }
+        }
+        junta_subtours(&g, &mut adj, &candidatos);
+        assert_eq!(subtours(&adj).1.len(), 1);
+        confere_permutacao(&percorre(&adj), 17);
+    }
+}
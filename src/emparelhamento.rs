@@ -0,0 +1,650 @@
+use std::mem;
+use std::usize;
+use grafo::Vertice;
+
+const NENHUM: usize = usize::MAX;
+
+// Rótulos da floresta alternante. MARCADO é usado temporariamente por
+// `busca_flor` para achar o ancestral comum.
+const LIVRE: u8 = 0;
+const EXTERNO: u8 = 1;
+const INTERNO: u8 = 2;
+const MARCADO: u8 = 5;
+
+// Emparelhamento de peso máximo em grafos gerais pelo algoritmo de Edmonds com
+// flores, na versão O(n³) de Galil (a mesma estrutura do mwmatching.py de Van
+// Rantwijk). Os vértices são 0..n e as flores n..2n; cada aresta k tem dois
+// extremos, 2k e 2k + 1, e `par` guarda o extremo remoto da aresta emparelhada.
+struct Emparelhamento {
+    n: usize,
+    arestas: Vec<(Vertice, Vertice, f64)>,
+    extremo: Vec<Vertice>,
+    vizinhanca: Vec<Vec<usize>>,
+    par: Vec<usize>,
+    rotulo: Vec<u8>,
+    fim_rotulo: Vec<usize>,
+    na_flor: Vec<usize>,
+    pai: Vec<usize>,
+    filhos: Vec<Vec<usize>>,
+    base: Vec<Vertice>,
+    extremos: Vec<Vec<usize>>,
+    melhor_aresta: Vec<usize>,
+    melhores_arestas: Vec<Option<Vec<usize>>>,
+    livres: Vec<usize>,
+    dual: Vec<f64>,
+    permitida: Vec<bool>,
+    fila: Vec<Vertice>,
+    tolerancia: f64,
+}
+
+// Índice circular, como os índices negativos das listas de filhos no original
+fn circular(j: isize, tamanho: usize) -> usize {
+    let t = tamanho as isize;
+    (((j % t) + t) % t) as usize
+}
+
+impl Emparelhamento {
+    fn new(n: usize, arestas: Vec<(Vertice, Vertice, f64)>) -> Emparelhamento {
+        let peso_maximo = arestas.iter().fold(0.0f64, |m, &(_, _, w)| m.max(w));
+        let mut extremo = Vec::with_capacity(2 * arestas.len());
+        let mut vizinhanca = vec![vec![]; n];
+        for (k, &(i, j, _)) in arestas.iter().enumerate() {
+            extremo.push(i);
+            extremo.push(j);
+            vizinhanca[i].push(2 * k + 1);
+            vizinhanca[j].push(2 * k);
+        }
+        let m = arestas.len();
+
+        Emparelhamento {
+            n: n,
+            arestas: arestas,
+            extremo: extremo,
+            vizinhanca: vizinhanca,
+            par: vec![NENHUM; n],
+            rotulo: vec![LIVRE; 2 * n],
+            fim_rotulo: vec![NENHUM; 2 * n],
+            na_flor: (0..n).collect(),
+            pai: vec![NENHUM; 2 * n],
+            filhos: vec![vec![]; 2 * n],
+            base: (0..n).chain((0..n).map(|_| NENHUM)).collect(),
+            extremos: vec![vec![]; 2 * n],
+            melhor_aresta: vec![NENHUM; 2 * n],
+            melhores_arestas: vec![None; 2 * n],
+            livres: (n..2 * n).collect(),
+            dual: (0..2 * n).map(|b| if b < n { peso_maximo } else { 0.0 }).collect(),
+            permitida: vec![false; m],
+            fila: vec![],
+            tolerancia: 1e-9 * peso_maximo.max(1.0),
+        }
+    }
+
+    fn folga(&self, k: usize) -> f64 {
+        let (i, j, w) = self.arestas[k];
+        self.dual[i] + self.dual[j] - 2.0 * w
+    }
+
+    // Vértices contidos na flor `b`, na ordem dos filhos
+    fn folhas(&self, b: usize) -> Vec<Vertice> {
+        let mut folhas = vec![];
+        let mut pilha = vec![b];
+        while let Some(t) = pilha.pop() {
+            if t < self.n {
+                folhas.push(t);
+            } else {
+                pilha.extend(self.filhos[t].iter().rev().cloned());
+            }
+        }
+        folhas
+    }
+
+    // Rotula o vértice `w` e sua flor, alcançados pelo extremo `p`. Um rótulo
+    // interno propaga o rótulo externo para o par da base.
+    fn rotula(&mut self, mut w: Vertice, mut t: u8, mut p: usize) {
+        loop {
+            let b = self.na_flor[w];
+            self.rotulo[w] = t;
+            self.rotulo[b] = t;
+            self.fim_rotulo[w] = p;
+            self.fim_rotulo[b] = p;
+            self.melhor_aresta[w] = NENHUM;
+            self.melhor_aresta[b] = NENHUM;
+            if t == EXTERNO {
+                let folhas = self.folhas(b);
+                self.fila.extend(folhas);
+                return;
+            }
+            let m = self.par[self.base[b]];
+            w = self.extremo[m];
+            t = EXTERNO;
+            p = m ^ 1;
+        }
+    }
+
+    // Sobe pelas árvores de `v` e `w` alternadamente. Devolve a base da nova
+    // flor, ou NENHUM se as raízes são diferentes e há um caminho de aumento.
+    fn busca_flor(&mut self, mut v: Vertice, mut w: Vertice) -> Vertice {
+        let mut caminho = vec![];
+        let mut base = NENHUM;
+        while v != NENHUM || w != NENHUM {
+            let mut b = self.na_flor[v];
+            if self.rotulo[b] & 4 != 0 {
+                base = self.base[b];
+                break;
+            }
+            caminho.push(b);
+            self.rotulo[b] = MARCADO;
+            if self.fim_rotulo[b] == NENHUM {
+                v = NENHUM;
+            } else {
+                v = self.extremo[self.fim_rotulo[b]];
+                b = self.na_flor[v];
+                v = self.extremo[self.fim_rotulo[b]];
+            }
+            if w != NENHUM {
+                mem::swap(&mut v, &mut w);
+            }
+        }
+        for b in caminho {
+            self.rotulo[b] = EXTERNO;
+        }
+        base
+    }
+
+    // Contrai o ciclo ímpar fechado pela aresta `k` numa nova flor
+    fn adiciona_flor(&mut self, base: Vertice, k: usize) {
+        let (mut v, mut w, _) = self.arestas[k];
+        let bb = self.na_flor[base];
+        let mut bv = self.na_flor[v];
+        let mut bw = self.na_flor[w];
+        let b = self.livres.pop().unwrap();
+        self.base[b] = base;
+        self.pai[b] = NENHUM;
+        self.pai[bb] = b;
+
+        let mut caminho = vec![];
+        let mut extremos = vec![];
+        while bv != bb {
+            self.pai[bv] = b;
+            caminho.push(bv);
+            extremos.push(self.fim_rotulo[bv]);
+            v = self.extremo[self.fim_rotulo[bv]];
+            bv = self.na_flor[v];
+        }
+        caminho.push(bb);
+        caminho.reverse();
+        extremos.reverse();
+        extremos.push(2 * k);
+        while bw != bb {
+            self.pai[bw] = b;
+            caminho.push(bw);
+            extremos.push(self.fim_rotulo[bw] ^ 1);
+            w = self.extremo[self.fim_rotulo[bw]];
+            bw = self.na_flor[w];
+        }
+
+        self.rotulo[b] = EXTERNO;
+        self.fim_rotulo[b] = self.fim_rotulo[bb];
+        self.dual[b] = 0.0;
+        self.filhos[b] = caminho.clone();
+        self.extremos[b] = extremos;
+        for v in self.folhas(b) {
+            if self.rotulo[self.na_flor[v]] == INTERNO {
+                self.fila.push(v);
+            }
+            self.na_flor[v] = b;
+        }
+
+        // Melhor aresta da nova flor para cada flor externa vizinha
+        let mut melhor_para = vec![NENHUM; 2 * self.n];
+        for &bv in &caminho {
+            let listas = match self.melhores_arestas[bv].take() {
+                Some(lista) => vec![lista],
+                None => {
+                    self.folhas(bv)
+                        .iter()
+                        .map(|&v| self.vizinhanca[v].iter().map(|&p| p / 2).collect())
+                        .collect()
+                }
+            };
+            for lista in listas {
+                for k in lista {
+                    let (i, j, _) = self.arestas[k];
+                    let j = if self.na_flor[j] == b { i } else { j };
+                    let bj = self.na_flor[j];
+                    if bj != b && self.rotulo[bj] == EXTERNO &&
+                       (melhor_para[bj] == NENHUM || self.folga(k) < self.folga(melhor_para[bj])) {
+                        melhor_para[bj] = k;
+                    }
+                }
+            }
+            self.melhor_aresta[bv] = NENHUM;
+        }
+        let lista = melhor_para.into_iter().filter(|&k| k != NENHUM).collect::<Vec<_>>();
+        self.melhor_aresta[b] = NENHUM;
+        for &k in &lista {
+            let melhor = self.melhor_aresta[b];
+            if melhor == NENHUM || self.folga(k) < self.folga(melhor) {
+                self.melhor_aresta[b] = k;
+            }
+        }
+        self.melhores_arestas[b] = Some(lista);
+    }
+
+    // Desfaz a flor `b`. Durante uma etapa, os filhos no caminho par até a base
+    // recebem novos rótulos.
+    fn expande(&mut self, b: usize, fim_etapa: bool) {
+        let filhos = self.filhos[b].clone();
+        for &s in &filhos {
+            self.pai[s] = NENHUM;
+            if s < self.n {
+                self.na_flor[s] = s;
+            } else if fim_etapa && self.dual[s] <= self.tolerancia {
+                self.expande(s, fim_etapa);
+            } else {
+                for v in self.folhas(s) {
+                    self.na_flor[v] = s;
+                }
+            }
+        }
+
+        if !fim_etapa && self.rotulo[b] == INTERNO {
+            let tamanho = filhos.len();
+            let entrada = self.na_flor[self.extremo[self.fim_rotulo[b] ^ 1]];
+            let mut j = filhos.iter().position(|&t| t == entrada).unwrap() as isize;
+            let (passo, truque) = if j & 1 != 0 {
+                j -= tamanho as isize;
+                (1, 0)
+            } else {
+                (-1, 1)
+            };
+
+            let mut p = self.fim_rotulo[b];
+            while j != 0 {
+                let q = self.extremos[b][circular(j - truque as isize, tamanho)];
+                let (a, c) = (self.extremo[p ^ 1], self.extremo[q ^ truque ^ 1]);
+                self.rotulo[a] = LIVRE;
+                self.rotulo[c] = LIVRE;
+                self.rotula(a, INTERNO, p);
+                self.permitida[q / 2] = true;
+                j += passo;
+                p = self.extremos[b][circular(j - truque as isize, tamanho)] ^ truque;
+                self.permitida[p / 2] = true;
+                j += passo;
+            }
+
+            let bv = filhos[circular(j, tamanho)];
+            let a = self.extremo[p ^ 1];
+            self.rotulo[a] = INTERNO;
+            self.rotulo[bv] = INTERNO;
+            self.fim_rotulo[a] = p;
+            self.fim_rotulo[bv] = p;
+            self.melhor_aresta[bv] = NENHUM;
+            j += passo;
+            while filhos[circular(j, tamanho)] != entrada {
+                let bv = filhos[circular(j, tamanho)];
+                j += passo;
+                if self.rotulo[bv] == EXTERNO {
+                    continue;
+                }
+                let alcancado = self.folhas(bv).into_iter().find(|&v| self.rotulo[v] != LIVRE);
+                if let Some(v) = alcancado {
+                    let c = self.extremo[self.par[self.base[bv]]];
+                    self.rotulo[v] = LIVRE;
+                    self.rotulo[c] = LIVRE;
+                    let p = self.fim_rotulo[v];
+                    self.rotula(v, INTERNO, p);
+                }
+            }
+        }
+
+        self.rotulo[b] = LIVRE;
+        self.fim_rotulo[b] = NENHUM;
+        self.filhos[b] = vec![];
+        self.extremos[b] = vec![];
+        self.base[b] = NENHUM;
+        self.melhores_arestas[b] = None;
+        self.melhor_aresta[b] = NENHUM;
+        self.livres.push(b);
+    }
+
+    // Troca as arestas emparelhadas no caminho par de `v` até a base de `b`,
+    // que passa a ser `v`
+    fn aumenta_flor(&mut self, b: usize, v: Vertice) {
+        let mut t = v;
+        while self.pai[t] != b {
+            t = self.pai[t];
+        }
+        if t >= self.n {
+            self.aumenta_flor(t, v);
+        }
+
+        let tamanho = self.filhos[b].len();
+        let i = self.filhos[b].iter().position(|&s| s == t).unwrap();
+        let mut j = i as isize;
+        let (passo, truque) = if j & 1 != 0 {
+            j -= tamanho as isize;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+        while j != 0 {
+            j += passo;
+            let t = self.filhos[b][circular(j, tamanho)];
+            let p = self.extremos[b][circular(j - truque as isize, tamanho)] ^ truque;
+            if t >= self.n {
+                let e = self.extremo[p];
+                self.aumenta_flor(t, e);
+            }
+            j += passo;
+            let t = self.filhos[b][circular(j, tamanho)];
+            if t >= self.n {
+                let e = self.extremo[p ^ 1];
+                self.aumenta_flor(t, e);
+            }
+            let (a, c) = (self.extremo[p], self.extremo[p ^ 1]);
+            self.par[a] = p ^ 1;
+            self.par[c] = p;
+        }
+
+        let filhos = self.filhos[b][i..].iter().chain(&self.filhos[b][..i]).cloned().collect();
+        let extremos =
+            self.extremos[b][i..].iter().chain(&self.extremos[b][..i]).cloned().collect();
+        self.filhos[b] = filhos;
+        self.extremos[b] = extremos;
+        self.base[b] = self.base[self.filhos[b][0]];
+    }
+
+    // Aumenta o emparelhamento pelo caminho que passa pela aresta `k`
+    fn aumenta(&mut self, k: usize) {
+        let (v, w, _) = self.arestas[k];
+        for &(s, p) in &[(v, 2 * k + 1), (w, 2 * k)] {
+            let (mut s, mut p) = (s, p);
+            loop {
+                let bs = self.na_flor[s];
+                if bs >= self.n {
+                    self.aumenta_flor(bs, s);
+                }
+                self.par[s] = p;
+                if self.fim_rotulo[bs] == NENHUM {
+                    break;
+                }
+                let t = self.extremo[self.fim_rotulo[bs]];
+                let bt = self.na_flor[t];
+                s = self.extremo[self.fim_rotulo[bt]];
+                let j = self.extremo[self.fim_rotulo[bt] ^ 1];
+                if bt >= self.n {
+                    self.aumenta_flor(bt, j);
+                }
+                self.par[j] = self.fim_rotulo[bt];
+                p = self.fim_rotulo[bt] ^ 1;
+            }
+        }
+    }
+
+    // Examina as arestas dos vértices externos na fila. Devolve true se o
+    // emparelhamento foi aumentado.
+    fn processa_fila(&mut self) -> bool {
+        while let Some(v) = self.fila.pop() {
+            for indice in 0..self.vizinhanca[v].len() {
+                let p = self.vizinhanca[v][indice];
+                let k = p / 2;
+                let w = self.extremo[p];
+                if self.na_flor[v] == self.na_flor[w] {
+                    continue;
+                }
+                let mut folga = 0.0;
+                if !self.permitida[k] {
+                    folga = self.folga(k);
+                    if folga <= self.tolerancia {
+                        self.permitida[k] = true;
+                    }
+                }
+
+                let bw = self.na_flor[w];
+                if self.permitida[k] {
+                    if self.rotulo[bw] == LIVRE {
+                        self.rotula(w, INTERNO, p ^ 1);
+                    } else if self.rotulo[bw] == EXTERNO {
+                        let base = self.busca_flor(v, w);
+                        if base != NENHUM {
+                            self.adiciona_flor(base, k);
+                        } else {
+                            self.aumenta(k);
+                            return true;
+                        }
+                    } else if self.rotulo[w] == LIVRE {
+                        self.rotulo[w] = INTERNO;
+                        self.fim_rotulo[w] = p ^ 1;
+                    }
+                } else if self.rotulo[bw] == EXTERNO {
+                    let b = self.na_flor[v];
+                    let melhor = self.melhor_aresta[b];
+                    if melhor == NENHUM || folga < self.folga(melhor) {
+                        self.melhor_aresta[b] = k;
+                    }
+                } else if self.rotulo[w] == LIVRE &&
+                          (self.melhor_aresta[w] == NENHUM ||
+                           folga < self.folga(self.melhor_aresta[w])) {
+                    self.melhor_aresta[w] = k;
+                }
+            }
+        }
+        false
+    }
+
+    // Ajusta as variáveis duais pelo maior passo que mantém a viabilidade.
+    // Devolve false quando nenhum aumento é mais possível.
+    fn atualiza_duais(&mut self) -> bool {
+        let n = self.n;
+        let mut tipo = 0;
+        let mut delta = 0.0;
+        let mut aresta = NENHUM;
+        let mut flor = NENHUM;
+
+        for v in 0..n {
+            let k = self.melhor_aresta[v];
+            if self.rotulo[self.na_flor[v]] == LIVRE && k != NENHUM {
+                let d = self.folga(k);
+                if tipo == 0 || d < delta {
+                    delta = d;
+                    tipo = 2;
+                    aresta = k;
+                }
+            }
+        }
+        for b in 0..2 * n {
+            let k = self.melhor_aresta[b];
+            if self.pai[b] == NENHUM && self.rotulo[b] == EXTERNO && k != NENHUM {
+                let d = self.folga(k) / 2.0;
+                if tipo == 0 || d < delta {
+                    delta = d;
+                    tipo = 3;
+                    aresta = k;
+                }
+            }
+        }
+        for b in n..2 * n {
+            if self.base[b] != NENHUM && self.pai[b] == NENHUM && self.rotulo[b] == INTERNO &&
+               (tipo == 0 || self.dual[b] < delta) {
+                delta = self.dual[b];
+                tipo = 4;
+                flor = b;
+            }
+        }
+        if tipo == 0 {
+            tipo = 1;
+            delta = self.dual[..n].iter().fold(f64::INFINITY, |m, &d| m.min(d)).max(0.0);
+        }
+
+        for v in 0..n {
+            match self.rotulo[self.na_flor[v]] {
+                EXTERNO => self.dual[v] -= delta,
+                INTERNO => self.dual[v] += delta,
+                _ => {}
+            }
+        }
+        for b in n..2 * n {
+            if self.base[b] != NENHUM && self.pai[b] == NENHUM {
+                match self.rotulo[b] {
+                    EXTERNO => self.dual[b] += delta,
+                    INTERNO => self.dual[b] -= delta,
+                    _ => {}
+                }
+            }
+        }
+
+        match tipo {
+            1 => return false,
+            2 => {
+                self.permitida[aresta] = true;
+                let (i, j, _) = self.arestas[aresta];
+                let i = if self.rotulo[self.na_flor[i]] == LIVRE { j } else { i };
+                self.fila.push(i);
+            }
+            3 => {
+                self.permitida[aresta] = true;
+                let (i, _, _) = self.arestas[aresta];
+                self.fila.push(i);
+            }
+            _ => self.expande(flor, false),
+        }
+        true
+    }
+
+    // Emparelhamento de peso máximo entre os de cardinalidade máxima
+    fn resolve(mut self) -> Vec<Vertice> {
+        let n = self.n;
+        for _ in 0..n {
+            // Nova etapa: a floresta é reconstruída a partir dos vértices livres
+            for r in &mut self.rotulo {
+                *r = LIVRE;
+            }
+            for k in &mut self.melhor_aresta {
+                *k = NENHUM;
+            }
+            for lista in &mut self.melhores_arestas[n..] {
+                *lista = None;
+            }
+            for p in &mut self.permitida {
+                *p = false;
+            }
+            self.fila.clear();
+            for v in 0..n {
+                if self.par[v] == NENHUM && self.rotulo[self.na_flor[v]] == LIVRE {
+                    self.rotula(v, EXTERNO, NENHUM);
+                }
+            }
+
+            let mut aumentou = false;
+            loop {
+                if self.processa_fila() {
+                    aumentou = true;
+                    break;
+                }
+                if !self.atualiza_duais() {
+                    break;
+                }
+            }
+            if !aumentou {
+                break;
+            }
+
+            for b in n..2 * n {
+                if self.pai[b] == NENHUM && self.base[b] != NENHUM && self.rotulo[b] == EXTERNO &&
+                   self.dual[b] <= self.tolerancia {
+                    self.expande(b, true);
+                }
+            }
+        }
+
+        (0..n)
+            .map(|v| if self.par[v] == NENHUM { NENHUM } else { self.extremo[self.par[v]] })
+            .collect()
+    }
+}
+
+// Emparelhamento perfeito de custo mínimo no grafo completo de `n` vértices,
+// com `n` par. Devolve o par de cada vértice.
+pub fn perfeito_minimo<F>(n: usize, custo: F) -> Vec<Vertice>
+    where F: Fn(Vertice, Vertice) -> f64
+{
+    assert!(n % 2 == 0, "perfect matching needs an even number of vertices");
+    let mut arestas = Vec::with_capacity(n * n.saturating_sub(1) / 2);
+    for i in 0..n {
+        for j in i + 1..n {
+            arestas.push((i, j, custo(i, j)));
+        }
+    }
+
+    // Como todo emparelhamento perfeito tem n / 2 arestas, maximizar
+    // `maior - custo` entre os de cardinalidade máxima minimiza o custo
+    let maior = arestas.iter().fold(0.0f64, |m, &(_, _, c)| m.max(c));
+    for aresta in &mut arestas {
+        aresta.2 = maior - aresta.2;
+    }
+    Emparelhamento::new(n, arestas).resolve()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate rand;
+
+    use super::*;
+    use self::rand::Rng;
+
+    fn custo_total<F: Fn(Vertice, Vertice) -> f64>(par: &[Vertice], custo: F) -> f64 {
+        (0..par.len()).filter(|&i| i < par[i]).map(|i| custo(i, par[i])).sum()
+    }
+
+    // Menor emparelhamento perfeito por força bruta
+    fn forca_bruta(restantes: &[Vertice], custo: &[Vec<f64>]) -> f64 {
+        if restantes.is_empty() {
+            return 0.0;
+        }
+        let i = restantes[0];
+        (1..restantes.len())
+            .map(|a| {
+                let resto = restantes[1..]
+                    .iter()
+                    .enumerate()
+                    .filter(|&(b, _)| b + 1 != a)
+                    .map(|(_, &v)| v)
+                    .collect::<Vec<_>>();
+                custo[i][restantes[a]] + forca_bruta(&resto, custo)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn perfeito_e_valido() {
+        let par = perfeito_minimo(6, |i, j| (i as f64 - j as f64).abs());
+        for v in 0..6 {
+            assert!(par[v] != v && par[v] < 6);
+            assert_eq!(par[par[v]], v);
+        }
+        assert_eq!(custo_total(&par, |i, j| (i as f64 - j as f64).abs()), 3.0);
+    }
+
+    #[test]
+    fn igual_forca_bruta() {
+        let mut rng = rand::weak_rng();
+        for &n in &[2, 4, 6, 8, 10] {
+            for _ in 0..30 {
+                let mut custo = vec![vec![0.0; n]; n];
+                for i in 0..n {
+                    for j in i + 1..n {
+                        // Pesos inteiros e reais
+                        let c = if rng.gen() { rng.gen_range(1, 20) as f64 } else { rng.gen() };
+                        custo[i][j] = c;
+                        custo[j][i] = c;
+                    }
+                }
+                let par = perfeito_minimo(n, |i, j| custo[i][j]);
+                let obtido = custo_total(&par, |i, j| custo[i][j]);
+                let otimo = forca_bruta(&(0..n).collect::<Vec<_>>(), &custo);
+                assert!((obtido - otimo).abs() < 1e-9, "{} != {}", obtido, otimo);
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum ErroInstancia {
+    Io(io::Error),
+    Parse {
+        linha: usize,
+        coluna: usize,
+        token: String,
+    },
+    NaoQuadrada {
+        linha: usize,
+        esperado: usize,
+        encontrado: usize,
+    },
+    Dimensao {
+        declarada: usize,
+        encontrada: usize,
+    },
+    NumeroPesos {
+        esperado: usize,
+        encontrado: usize,
+    },
+    Vazia,
+    Formato(String),
+}
+
+impl fmt::Display for ErroInstancia {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErroInstancia::Io(ref e) => write!(f, "I/O error: {}", e),
+            ErroInstancia::Parse { linha, coluna, ref token } => {
+                write!(f, "invalid token '{}' at line {}, column {}", token, linha, coluna)
+            }
+            ErroInstancia::NaoQuadrada { linha, esperado, encontrado } => {
+                write!(f,
+                       "matrix is not square: line {} has {} values, expected {}",
+                       linha,
+                       encontrado,
+                       esperado)
+            }
+            ErroInstancia::Dimensao { declarada, encontrada } => {
+                write!(f,
+                       "DIMENSION is {} but {} vertices were found",
+                       declarada,
+                       encontrada)
+            }
+            ErroInstancia::NumeroPesos { esperado, encontrado } => {
+                write!(f,
+                       "expected {} edge weights, found {}",
+                       esperado,
+                       encontrado)
+            }
+            ErroInstancia::Vazia => write!(f, "instance has no vertices"),
+            ErroInstancia::Formato(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for ErroInstancia {
+    fn description(&self) -> &str {
+        match *self {
+            ErroInstancia::Io(_) => "I/O error",
+            ErroInstancia::Parse { .. } => "invalid token",
+            ErroInstancia::NaoQuadrada { .. } => "matrix is not square",
+            ErroInstancia::Dimensao { .. } => "DIMENSION mismatch",
+            ErroInstancia::NumeroPesos { .. } => "wrong number of edge weights",
+            ErroInstancia::Vazia => "instance has no vertices",
+            ErroInstancia::Formato(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            ErroInstancia::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ErroInstancia {
+    fn from(e: io::Error) -> ErroInstancia {
+        ErroInstancia::Io(e)
+    }
+}
+
+// Tokens separados por espaço, junto com a coluna (começando em 1) onde aparecem
+pub fn tokens(linha: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut inicio = None;
+
+    for (i, c) in linha.char_indices() {
+        match (c.is_whitespace(), inicio) {
+            (true, Some(ini)) => {
+                tokens.push((ini + 1, &linha[ini..i]));
+                inicio = None;
+            }
+            (false, None) => inicio = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(ini) = inicio {
+        tokens.push((ini + 1, &linha[ini..]));
+    }
+
+    tokens
+}
+
+pub fn parse<T: FromStr>(token: &str, linha: usize, coluna: usize) -> Result<T, ErroInstancia> {
+    token.parse().map_err(|_| {
+        ErroInstancia::Parse {
+            linha: linha,
+            coluna: coluna,
+            token: token.to_string(),
+        }
+    })
+}
+
+#[derive(Debug)]
+pub enum ErroConstrucao {
+    Infactivel(String),
+    DificilConstruir { tentativas: u32 },
+    MemoriaInsuficiente { necessaria: usize, limite: usize },
+    NaoSuportada(String),
+    NaoMetrica { violacoes: usize },
+    ParametroInvalido(String),
+}
+
+impl fmt::Display for ErroConstrucao {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErroConstrucao::Infactivel(ref motivo) => {
+                write!(f, "instance has no feasible tour: {}", motivo)
+            }
+            ErroConstrucao::DificilConstruir { tentativas } => {
+                write!(f,
+                       "no feasible tour found after {} attempts and a backtracking search",
+                       tentativas)
+            }
+            ErroConstrucao::MemoriaInsuficiente { necessaria, limite } => {
+                write!(f,
+                       "solver needs {} bytes but the memory limit is {} bytes",
+                       necessaria,
+                       limite)
+            }
+            ErroConstrucao::NaoSuportada(ref motivo) => {
+                write!(f, "instance not supported by this solver: {}", motivo)
+            }
+            ErroConstrucao::NaoMetrica { violacoes } => {
+                write!(f,
+                       "instance violates the triangle inequality {} times",
+                       violacoes)
+            }
+            ErroConstrucao::ParametroInvalido(ref motivo) => {
+                write!(f, "invalid solver parameter: {}", motivo)
+            }
+        }
+    }
+}
+
+impl Error for ErroConstrucao {
+    fn description(&self) -> &str {
+        match *self {
+            ErroConstrucao::Infactivel(_) => "instance has no feasible tour",
+            ErroConstrucao::DificilConstruir { .. } => "feasible tour is hard to construct",
+            ErroConstrucao::MemoriaInsuficiente { .. } => "solver exceeds the memory limit",
+            ErroConstrucao::NaoSuportada(_) => "instance not supported by this solver",
+            ErroConstrucao::NaoMetrica { .. } => "instance violates the triangle inequality",
+            ErroConstrucao::ParametroInvalido(_) => "invalid solver parameter",
+        }
+    }
+}
@@ -3,9 +3,12 @@ use std::io;
 // use std::io::prelude::*;
 use std::path::Path;
 use std::fs::File;
-use std::iter::Enumerate;
-use std::slice::Iter;
 use std::collections::VecDeque;
+use tsplib;
+use erro::{self, ErroInstancia};
+use coordenadas::Coordenadas;
+use instancia::Instancia;
+use custo::Custo;
 
 pub const INF: u64 = 1e9 as u64;
 
@@ -14,79 +17,191 @@ pub type Vertice = usize;
 // pub type Grafo = Vec<Vec<Peso>>;
 pub type Caminho = Vec<Vertice>;
 
+// Uma instância guarda a matriz de distâncias, as coordenadas dos vértices ou
+// ambas. Sem a matriz, as distâncias são calculadas a partir das coordenadas.
+// Os pesos são inteiros por padrão; com `Grafo<f64>` são reais.
 #[derive(Clone)]
 #[derive(Debug)]
-pub struct Grafo(Vec<Vec<Peso>>);
+pub struct Grafo<P: Custo = Peso> {
+    matriz: Option<Vec<Vec<P>>>,
+    coordenadas: Option<Coordenadas>,
+}
 
-impl Grafo {
-    pub fn num_vertices(&self) -> usize {
-        self.0.len()
+impl<P: Custo> Instancia for Grafo<P> {
+    type Peso = P;
+
+    fn num_vertices(&self) -> usize {
+        match (&self.matriz, &self.coordenadas) {
+            (&Some(ref m), _) => m.len(),
+            (&None, &Some(ref c)) => c.num_vertices(),
+            (&None, &None) => 0,
+        }
     }
 
-    pub fn adjacentes(&self, vertice: Vertice) -> Enumerate<Iter<u64>> {
-        self.0[vertice].iter().enumerate()
+    fn distancia(&self, src: Vertice, dst: Vertice) -> P {
+        match (&self.matriz, &self.coordenadas) {
+            (&Some(ref m), _) => m[src][dst],
+            (&None, &Some(_)) if src == dst => P::zero(),
+            (&None, &Some(ref c)) => {
+                let pontos = c.pontos();
+                P::da_metrica(&c.metrica(), &pontos[src], &pontos[dst])
+            }
+            (&None, &None) => panic!("Empty graph"),
+        }
     }
 
-    pub fn distancia(&self, src: Vertice, dst: Vertice) -> Peso {
-        self.0[src][dst]
+    fn coordenadas(&self) -> Option<&Coordenadas> {
+        self.coordenadas.as_ref()
     }
+}
 
+impl Grafo {
     #[cfg_attr(rustfmt, rustfmt_skip)]
     pub fn toy() -> Grafo {
-        Grafo(vec![
+        Grafo::from_matriz(vec![
             vec![0, 1, 4, 2],
             vec![1, 0, 2, 5],
             vec![4, 2, 0, 3],
             vec![2, 5, 3, 0]
         ])
     }
+}
+
+impl<P: Custo> Grafo<P> {
+    pub fn is_quadrada(&self) -> bool {
+        match self.matriz {
+            Some(ref m) => m.iter().all(|linha| linha.len() == m.len()),
+            None => true,
+        }
+    }
+
+    // Pré-calcula a matriz de distâncias, mantendo as coordenadas
+    pub fn materializa(self) -> Grafo<P> {
+        if self.matriz.is_some() {
+            return self;
+        }
+        let n = self.num_vertices();
+        let matriz = (0..n)
+            .map(|i| (0..n).map(|j| self.distancia(i, j)).collect())
+            .collect();
+        Grafo {
+            matriz: Some(matriz),
+            coordenadas: self.coordenadas,
+        }
+    }
+
+    pub fn from_matriz(matriz: Vec<Vec<P>>) -> Grafo<P> {
+        Grafo {
+            matriz: Some(matriz),
+            coordenadas: None,
+        }
+    }
+
+    pub fn from_coordenadas(coordenadas: Coordenadas) -> Grafo<P> {
+        Grafo {
+            matriz: None,
+            coordenadas: Some(coordenadas),
+        }
+    }
 
     #[allow(dead_code)]
-    pub fn from_stdin() -> Grafo {
+    pub fn from_stdin() -> Result<Grafo<P>, ErroInstancia> {
         let stdin = io::stdin();
         let mut stdin = stdin.lock();
         let mut buf = String::new();
 
-        stdin.read_line(&mut buf).expect("Unable to read number of vertexes");
-        let n = buf.trim().parse().unwrap_or(0);
-
-        let x: Vec<Vec<u64>> = stdin.lines()
-            .take(n)
-            .map(|l| {
-                l.expect("Failed to read line")
-                    .split_whitespace()
-                    .take(n)
-                    .map(|x| x.parse().unwrap_or(0))
-                    .collect()
-            })
-            .collect();
+        stdin.read_line(&mut buf)?;
+        let n = match erro::tokens(&buf).first() {
+            Some(&(coluna, token)) => erro::parse(token, 1, coluna)?,
+            None => return Err(ErroInstancia::Vazia),
+        };
+
+        le_matriz(stdin, Some(n), 2)
+    }
 
-        Grafo(x)
+    pub fn from_leitor<R: BufRead>(leitor: R) -> Result<Grafo<P>, ErroInstancia> {
+        le_matriz(leitor, None, 1)
     }
 
-    pub fn from_arquivo(file: &str) -> Grafo {
+    pub fn from_arquivo(file: &str) -> Result<Grafo<P>, ErroInstancia> {
         let path = Path::new(file);
-        let file = BufReader::new(File::open(&path).expect("Failed to open file"));
+        Grafo::from_leitor(BufReader::new(File::open(&path)?))
+    }
+
+    pub fn from_tsplib(file: &str) -> Result<Grafo<P>, ErroInstancia> {
+        tsplib::from_arquivo(file).map(|(_, grafo)| grafo)
+    }
+}
 
-        Grafo(file.lines()
-            .map(|l| {
-                l.expect("Failed to read line")
-                    .split_whitespace()
-                    .map(|number| number.parse().unwrap_or(INF))
-                    .collect()
-            })
-            .collect())
+fn parse_peso<P: Custo>(token: &str, linha: usize, coluna: usize) -> Result<P, ErroInstancia> {
+    if token == "INF" {
+        Ok(P::inf())
+    } else {
+        erro::parse(token, linha, coluna)
     }
 }
 
+// Lê uma matriz quadrada, uma linha por vértice. Com `dimensao` definida, lê
+// apenas essa quantidade de linhas, sem consumir o resto da entrada.
+fn le_matriz<R, P>(leitor: R,
+                   dimensao: Option<usize>,
+                   linha_inicial: usize)
+                   -> Result<Grafo<P>, ErroInstancia>
+    where R: BufRead,
+          P: Custo
+{
+    let mut linhas = leitor.lines();
+    let mut num_linha = linha_inicial;
+    let mut matriz = Vec::new();
+
+    while dimensao.map_or(true, |n| matriz.len() < n) {
+        let linha = match linhas.next() {
+            Some(linha) => linha?,
+            None => break,
+        };
+
+        let mut pesos = Vec::new();
+        for (coluna, token) in erro::tokens(&linha) {
+            pesos.push(parse_peso(token, num_linha, coluna)?);
+        }
+        if !pesos.is_empty() {
+            matriz.push((num_linha, pesos));
+        }
+        num_linha += 1;
+    }
+
+    if let Some(n) = dimensao {
+        if matriz.len() != n {
+            return Err(ErroInstancia::Dimensao {
+                declarada: n,
+                encontrada: matriz.len(),
+            });
+        }
+    }
+    if matriz.is_empty() {
+        return Err(ErroInstancia::Vazia);
+    }
+
+    let n = matriz.len();
+    if let Some(&(linha, ref pesos)) = matriz.iter().find(|&&(_, ref pesos)| pesos.len() != n) {
+        return Err(ErroInstancia::NaoQuadrada {
+            linha: linha,
+            esperado: n,
+            encontrado: pesos.len(),
+        });
+    }
+
+    Ok(Grafo::from_matriz(matriz.into_iter().map(|(_, pesos)| pesos).collect()))
+}
+
 
 #[derive(Clone)]
-pub struct Solucao {
+pub struct Solucao<P = Peso> {
     caminho: Caminho,
-    fo: Peso,
+    fo: P,
 }
 
-fn is_factivel(c: &Caminho, num_vertices: usize) -> bool {
+pub fn is_factivel(c: &Caminho, num_vertices: usize) -> bool {
     c.len() == num_vertices && frequencias(c).into_iter().all(|n| n == 1)
 }
 
@@ -98,30 +213,43 @@ fn frequencias(caminho: &Caminho) -> Vec<u64> {
     freq
 }
 
-impl Solucao {
-    fn calcula_fo(grafo: &Grafo, caminho: &Caminho) -> Peso {
+impl<P: Custo> Solucao<P> {
+    fn calcula_fo<G>(grafo: &G, caminho: &Caminho) -> P
+        where G: Instancia<Peso = P>
+    {
         if !is_factivel(caminho, grafo.num_vertices()) {
-            return INF;
+            return P::inf();
         }
         let inicio = caminho[0];
         let fim = caminho[grafo.num_vertices() - 1];
         caminho.iter()
             .zip(&caminho[1..])
             .map(|(&src, &dst)| grafo.distancia(src, dst))
-            .sum::<Peso>() + grafo.distancia(fim, inicio)
+            .sum::<P>() + grafo.distancia(fim, inicio)
     }
 
-    pub fn new(grafo: &Grafo, caminho: Caminho) -> Solucao {
+    pub fn new<G>(grafo: &G, caminho: Caminho) -> Solucao<P>
+        where G: Instancia<Peso = P>
+    {
         Solucao {
             fo: Solucao::calcula_fo(grafo, &caminho),
             caminho: caminho,
         }
     }
 
+    // Solução cujo custo já é conhecido, por exemplo por avaliação incremental
+    // de um movimento. Não verifica a factibilidade do caminho.
+    pub fn com_fo(caminho: Caminho, fo: P) -> Solucao<P> {
+        Solucao {
+            caminho: caminho,
+            fo: fo,
+        }
+    }
+
     #[allow(dead_code)]
-    pub fn vazia() -> Solucao {
+    pub fn vazia() -> Solucao<P> {
         Solucao {
-            fo: INF,
+            fo: P::inf(),
             caminho: vec![],
         }
     }
@@ -130,7 +258,7 @@ impl Solucao {
         &self.caminho
     }
 
-    pub fn fo(&self) -> Peso {
+    pub fn fo(&self) -> P {
         self.fo
     }
 }
@@ -179,7 +307,7 @@ fn inv2perm(inv: &Caminho) -> Caminho {
 }
 
 #[allow(dead_code)]
-pub fn bfs_distancia(grafo: &Grafo, c: usize) -> Vec<usize> {
+pub fn bfs_distancia<G: Instancia>(grafo: &G, c: usize) -> Vec<usize> {
     let mut dist = vec![None; grafo.num_vertices()];
     let mut fila = VecDeque::new();
 
@@ -187,8 +315,8 @@ pub fn bfs_distancia(grafo: &Grafo, c: usize) -> Vec<usize> {
     fila.push_back(c);
 
     while let Some(i) = fila.pop_front() {
-        for (j, &peso) in grafo.adjacentes(i) {
-            if peso != 0 && dist[j].is_none() {
+        for j in 0..grafo.num_vertices() {
+            if grafo.aresta(i, j) && dist[j].is_none() {
                 dist[j] = dist[i].map(|d| d + 1);
                 fila.push_back(j);
             }
@@ -222,4 +350,50 @@ mod tests {
         let c = vec![0, 1, 2];
         assert_eq!(Solucao::calcula_fo(&g, &c), INF);
     }
+
+    #[test]
+    fn matriz_valida() {
+        let g: Grafo = Grafo::from_leitor("0 1 INF\n1 0 2\n\n3 2 0\n".as_bytes()).unwrap();
+        assert_eq!(g.num_vertices(), 3);
+        assert_eq!(g.distancia(0, 2), INF);
+        assert_eq!(g.distancia(2, 0), 3);
+    }
+
+    #[test]
+    fn matriz_real() {
+        let g = Grafo::<f64>::from_leitor("0 0.5 1.25\n0.5 0 0.1\n1.25 0.1 0\n".as_bytes())
+            .unwrap();
+        let s = Solucao::new(&g, vec![0, 1, 2]);
+        assert!(s.fo().igual(1.85));
+        assert!(!g.aresta(1, 1));
+        assert!(Solucao::new(&g, vec![0, 1]).fo().is_inf());
+    }
+
+    #[test]
+    fn matriz_token_invalido() {
+        match Grafo::<Peso>::from_leitor("0 1 2\n1 0 2\n2 x2 0\n".as_bytes()) {
+            Err(ErroInstancia::Parse { linha, coluna, token }) => {
+                assert_eq!((linha, coluna, token.as_str()), (3, 3, "x2"));
+            }
+            _ => panic!("esperava erro de parse"),
+        }
+    }
+
+    #[test]
+    fn matriz_nao_quadrada() {
+        match Grafo::<Peso>::from_leitor("0 1 2\n1 0\n2 1 0\n".as_bytes()) {
+            Err(ErroInstancia::NaoQuadrada { linha, esperado, encontrado }) => {
+                assert_eq!((linha, esperado, encontrado), (2, 3, 2));
+            }
+            _ => panic!("esperava matriz não quadrada"),
+        }
+    }
+
+    #[test]
+    fn arquivo_inexistente() {
+        match Grafo::<Peso>::from_arquivo("instances/nao_existe.txt") {
+            Err(ErroInstancia::Io(_)) => {}
+            _ => panic!("esperava erro de I/O"),
+        }
+    }
 }
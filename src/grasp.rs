@@ -3,31 +3,50 @@ extern crate rand;
 use std::u64;
 use std::time::{Duration, Instant};
 use self::rand::Rng;
-use grafo::{Solucao, Grafo, Caminho, Vertice};
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use construcao;
+use diagnostico;
+use busca_local::{Vnd, Vizinhanca};
+use candidatos::TipoCandidatos;
+use erro::ErroConstrucao;
 
 #[allow(dead_code)]
-pub fn solve(grafo: &Grafo,
-             alfa: f64, // 0.3 0.5 0.7
-             timeout: Duration, // 30s
-             num_vizinhos: u32, // 5 10 15
-             max_iter: u64)
-             -> (Solucao, u64) {
+pub fn solve<G: Instancia>(grafo: &G,
+                           alfa: f64, // 0.3 0.5 0.7
+                           timeout: Duration, // 30s
+                           num_vizinhos: u32, // 5 10 15
+                           max_iter: u64,
+                           semente: Option<&Caminho>,
+                           candidatos: Option<(TipoCandidatos, usize)>,
+                           vizinhancas: &[Vizinhanca])
+                           -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
     let mut rng = rand::weak_rng();
     let t = Instant::now();
 
+    let vnd = Vnd::new(grafo, vizinhancas, candidatos);
+
     let mut it = 0;
     let mut it_alvo = 0;
-    let mut best = Solucao::vazia();
+    let mut best = match semente {
+        Some(caminho) => {
+            let inicial = Solucao::new(grafo, caminho.clone());
+            busca_local(grafo, inicial, num_vizinhos, &vnd)
+        }
+        None => Solucao::vazia(),
+    };
 
     while it - it_alvo < max_iter && t.elapsed() < timeout {
         if it % max_iter == 0 {
             println!("i: {}", it);
         }
 
-        let atual = construcao(&mut rng, grafo, alfa);
-        let vizinho = busca_local(grafo, atual, num_vizinhos);
+        let atual = construcao(&mut rng, grafo, alfa)?;
+        let vizinho = busca_local(grafo, atual, num_vizinhos, &vnd);
 
-        if vizinho.fo() < best.fo() {
+        if vizinho.fo().menor(best.fo()) {
             best = vizinho;
             it_alvo = it;
         }
@@ -35,14 +54,14 @@ pub fn solve(grafo: &Grafo,
         it += 1;
     }
 
-    (best, it_alvo)
+    Ok((best, it_alvo))
 }
 
 #[allow(dead_code)]
-fn vizinho_mais_proximo<R: Rng + Sized>(mut rng: &mut R,
-                                        grafo: &Grafo,
-                                        alfa: f64)
-                                        -> Option<Caminho> {
+fn vizinho_mais_proximo<R, G>(mut rng: &mut R, grafo: &G, alfa: f64) -> Option<Caminho>
+    where R: Rng + Sized,
+          G: Instancia
+{
     let num_vertices = grafo.num_vertices();
     let mut caminho = Vec::with_capacity(num_vertices);
     let mut marcados = vec![false; num_vertices];
@@ -57,10 +76,10 @@ fn vizinho_mais_proximo<R: Rng + Sized>(mut rng: &mut R,
         let atual = caminho[caminho.len() - 1];
         let mut abertos = grafo.adjacentes(atual)
             .zip(marcados.iter())
-            .filter(|&((_, _), marc)| !marc)
-            .map(|((vert, &peso), _)| (vert, peso))
+            .filter(|&((_, peso), marc)| !marc && !peso.is_inf())
+            .map(|((vert, peso), _)| (vert, peso))
             .collect::<Vec<_>>();
-        abertos.sort_by(|&(_, a), &(_, b)| a.cmp(&b));
+        abertos.sort_by(|&(_, a), &(_, b)| a.compara(b));
 
         let num_candidatos = (abertos.len() as f64 * alfa).ceil() as usize;
         if num_candidatos == 0 {
@@ -73,110 +92,193 @@ fn vizinho_mais_proximo<R: Rng + Sized>(mut rng: &mut R,
         num_marcados += 1;
     }
 
-    Some(caminho)
-}
-
-#[allow(dead_code)]
-fn construcao<R: Rng + Sized>(mut rng: &mut R, grafo: &Grafo, alfa: f64) -> Solucao {
-    loop {
-        if let Some(caminho) = vizinho_mais_proximo(&mut rng, grafo, alfa) {
-            return Solucao::new(grafo, caminho);
-        }
-    }
-}
-fn busca_local_vizinho(grafo: &Grafo, solucao: &Solucao) -> Solucao {
-    let mut atual = solucao.clone();
-    while let Some(nova) = two_opt_loop(grafo, &atual) {
-        atual = nova;
+    if !grafo.aresta(caminho[num_vertices - 1], inicial) {
+        return None;
     }
-    atual
-}
 
-fn two_opt_swap(mut caminho: Caminho, i: Vertice, k: Vertice) -> Caminho {
-    caminho[i..k].reverse();
-    caminho
+    Some(caminho)
 }
 
 #[allow(dead_code)]
-fn two_opt_loop(grafo: &Grafo, solucao: &Solucao) -> Option<Solucao> {
-    let num_vertices = solucao.caminho().len();
-    let mut best = solucao.clone();
-
-    for i in 0..num_vertices - 1 {
-        for k in i + 1..num_vertices {
-            let nova = two_opt_swap(solucao.caminho().clone(), i, k);
-            let nova = Solucao::new(grafo, nova);
-            if nova.fo() < best.fo() {
-                best = nova;
-            }
-        }
-    }
-
-    if best.fo() < solucao.fo() {
-        Some(best)
-    } else {
-        None
-    }
+fn construcao<R, G>(rng: &mut R, grafo: &G, alfa: f64) -> Result<Solucao<G::Peso>, ErroConstrucao>
+    where R: Rng + Sized,
+          G: Instancia
+{
+    let caminho = construcao::com_retentativas(rng,
+                                               grafo,
+                                               |rng| vizinho_mais_proximo(rng, grafo, alfa))?;
+    Ok(Solucao::new(grafo, caminho))
 }
 
 #[allow(dead_code)]
-fn busca_local(grafo: &Grafo, s: Solucao, num_vizinhos: u32) -> Solucao {
+fn busca_local<G>(grafo: &G, s: Solucao<G::Peso>, num_vizinhos: u32, vnd: &Vnd) -> Solucao<G::Peso>
+    where G: Instancia
+{
     (0..num_vizinhos)
-        .map(|_| busca_local_vizinho(grafo, &s))
-        .min_by_key(Solucao::fo)
+        .map(|_| vnd.aplica(grafo, &s))
+        .min_by(|a, b| a.fo().compara(b.fo()))
         .unwrap_or(s)
 }
 
-pub struct Grasp<'a> {
-    grafo: &'a Grafo,
+pub struct Grasp<'a, G: Instancia + 'a> {
+    grafo: &'a G,
     alfa: f64,
     timeout: u64,
     num_vizinhos: u32,
     max_iter: u64,
+    semente: Option<Caminho>,
+    candidatos: Option<(TipoCandidatos, usize)>,
+    vizinhancas: Vec<Vizinhanca>,
 }
 
-impl<'a> Grasp<'a> {
+impl<'a, G: Instancia> Grasp<'a, G> {
     #[allow(dead_code)]
-    pub fn new(grafo: &Grafo) -> Grasp {
+    pub fn new(grafo: &G) -> Grasp<G> {
         Grasp {
             grafo: grafo,
             alfa: 0.35,
             timeout: u64::MAX,
             num_vizinhos: 10,
             max_iter: 40,
+            semente: None,
+            candidatos: None,
+            vizinhancas: vec![Vizinhanca::TwoOpt],
         }
     }
 
     #[allow(dead_code)]
-    pub fn alfa(&mut self, alfa: f64) -> &mut Grasp<'a> {
+    pub fn alfa(&mut self, alfa: f64) -> &mut Grasp<'a, G> {
         self.alfa = alfa;
         self
     }
 
     #[allow(dead_code)]
-    pub fn timeout(&mut self, timeout: u64) -> &mut Grasp<'a> {
+    pub fn timeout(&mut self, timeout: u64) -> &mut Grasp<'a, G> {
         self.timeout = timeout;
         self
     }
 
     #[allow(dead_code)]
-    pub fn num_vizinhos(&mut self, num_vizinhos: u32) -> &mut Grasp<'a> {
+    pub fn num_vizinhos(&mut self, num_vizinhos: u32) -> &mut Grasp<'a, G> {
         self.num_vizinhos = num_vizinhos;
         self
     }
 
     #[allow(dead_code)]
-    pub fn max_iter(&mut self, max_iter: u64) -> &mut Grasp<'a> {
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Grasp<'a, G> {
         self.max_iter = max_iter;
         self
     }
 
+    // Tour inicial, por exemplo de uma heurística construtiva
     #[allow(dead_code)]
-    pub fn solve(&self) -> (Solucao, u64) {
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Grasp<'a, G> {
+        self.semente = Some(caminho);
+        self
+    }
+
+    // Restringe 2-opt, Or-opt e 3-opt aos `k` vizinhos mais próximos de cada
+    // vértice, com don't-look bits. Necessário para instâncias com milhares de
+    // vértices.
+    #[allow(dead_code)]
+    pub fn candidatos(&mut self, k: usize) -> &mut Grasp<'a, G> {
+        self.candidatos = Some((TipoCandidatos::MaisProximos, k));
+        self
+    }
+
+    // Como `candidatos`, mas com vizinhos nos quatro quadrantes ao redor de
+    // cada vértice; exige coordenadas
+    #[allow(dead_code)]
+    pub fn candidatos_quadrantes(&mut self, k: usize) -> &mut Grasp<'a, G> {
+        self.candidatos = Some((TipoCandidatos::Quadrantes, k));
+        self
+    }
+
+    // Vizinhanças da busca local, na ordem do VND. Com uma só, é uma descida
+    // simples; o padrão é apenas 2-opt.
+    #[allow(dead_code)]
+    pub fn vizinhancas(&mut self, vizinhancas: Vec<Vizinhanca>) -> &mut Grasp<'a, G> {
+        self.vizinhancas = vizinhancas;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
         solve(self.grafo,
               self.alfa,
               Duration::from_secs(self.timeout),
               self.num_vizinhos,
-              self.max_iter)
+              self.max_iter,
+              self.semente.as_ref(),
+              self.candidatos,
+              &self.vizinhancas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_fo, confere_qualidade};
+
+    #[test]
+    fn com_candidatos() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let (s, _) = Grasp::new(&g).max_iter(5).candidatos(8).solve().unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 10);
+
+        // Assimétrica: volta ao 2-opt completo
+        let g = Grafo::from_matriz(vec![vec![0, 1, 9, 9],
+                                        vec![9, 0, 1, 9],
+                                        vec![9, 9, 0, 1],
+                                        vec![1, 9, 9, 0]]);
+        let (s, _) = Grasp::new(&g).max_iter(5).candidatos(2).solve().unwrap();
+        assert_eq!(s.fo(), 4);
+    }
+
+    #[test]
+    fn vnd_com_or_opt() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let (s, _) = Grasp::new(&g)
+            .max_iter(3)
+            .num_vizinhos(1)
+            .vizinhancas(vec![Vizinhanca::TwoOpt, Vizinhanca::OrOpt])
+            .solve()
+            .unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 10);
+
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let (s, _) = Grasp::new(&g)
+            .max_iter(3)
+            .vizinhancas(vec![Vizinhanca::OrOpt])
+            .solve()
+            .unwrap();
+        assert!(s.fo() >= 291);
+    }
+
+    #[test]
+    fn vnd_com_three_opt() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let (s, _) = Grasp::new(&g)
+            .max_iter(3)
+            .num_vizinhos(2)
+            .candidatos(10)
+            .vizinhancas(vec![Vizinhanca::TwoOpt, Vizinhanca::ThreeOpt])
+            .solve()
+            .unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 5);
+    }
+
+    #[test]
+    fn vnd_com_troca_e_insercao() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let (s, _) = Grasp::new(&g)
+            .max_iter(3)
+            .num_vizinhos(1)
+            .vizinhancas(vec![Vizinhanca::Insercao, Vizinhanca::Troca])
+            .solve()
+            .unwrap();
+        assert!(s.fo() >= 291);
+        confere_fo(&g, &s);
     }
 }
@@ -0,0 +1,220 @@
+extern crate rayon;
+
+use std::mem;
+use self::rayon::prelude::*;
+use grafo::{Solucao, Caminho};
+use instancia::Instancia;
+use custo::Custo;
+use diagnostico;
+use erro::ErroConstrucao;
+
+// 1 GiB
+const LIMITE_MEMORIA_PADRAO: usize = 1 << 30;
+
+// Memória da tabela dp: 2^(n-1) subconjuntos × (n-1) vértices finais
+fn memoria_necessaria<P>(num_vertices: usize) -> Option<usize> {
+    let m = num_vertices.saturating_sub(1);
+    if m >= mem::size_of::<usize>() * 8 {
+        return None;
+    }
+    (1usize << m).checked_mul(m).and_then(|x| x.checked_mul(mem::size_of::<P>()))
+}
+
+// Próximo número com a mesma quantidade de bits ligados (Gosper)
+fn proxima_combinacao(x: usize) -> usize {
+    let c = x & x.wrapping_neg();
+    let r = x + c;
+    (((r ^ x) >> 2) / c) | r
+}
+
+// Subconjuntos de {0..m} com k elementos, em ordem crescente
+fn camada(m: usize, k: usize) -> Vec<usize> {
+    if k == 0 {
+        return vec![0];
+    }
+    let mut mascaras = Vec::new();
+    let mut s = (1 << k) - 1;
+    while s < 1 << m {
+        mascaras.push(s);
+        s = proxima_combinacao(s);
+    }
+    mascaras
+}
+
+// O vértice 0 é a origem; o bit j da máscara representa o vértice j + 1.
+// dp[s * m + j]: menor caminho que sai de 0, visita exatamente `s` e termina em j + 1.
+fn linha<G: Instancia>(grafo: &G, dp: &[G::Peso], m: usize, s: usize) -> Vec<G::Peso> {
+    (0..m)
+        .map(|j| {
+            if s & (1 << j) == 0 {
+                return G::Peso::inf();
+            }
+            let anterior = s & !(1 << j);
+            if anterior == 0 {
+                return grafo.distancia(0, j + 1);
+            }
+            (0..m)
+                .filter(|&i| anterior & (1 << i) != 0)
+                .map(|i| dp[anterior * m + i] + grafo.distancia(i + 1, j + 1))
+                .min_by(|a, b| a.compara(*b))
+                .unwrap_or_else(G::Peso::inf)
+        })
+        .collect()
+}
+
+// Recupera o caminho recalculando qual predecessor atinge o valor de cada estado
+fn reconstroi<G: Instancia>(grafo: &G, dp: &[G::Peso], m: usize) -> Caminho {
+    let total = (1 << m) - 1;
+    let mut caminho = Vec::with_capacity(m + 1);
+    let mut j = (0..m)
+        .min_by(|&a, &b| {
+            (dp[total * m + a] + grafo.distancia(a + 1, 0))
+                .compara(dp[total * m + b] + grafo.distancia(b + 1, 0))
+        })
+        .unwrap();
+    let mut s = total;
+
+    loop {
+        caminho.push(j + 1);
+        let anterior = s & !(1 << j);
+        if anterior == 0 {
+            break;
+        }
+        let prox = (0..m)
+            .filter(|&i| anterior & (1 << i) != 0)
+            .min_by(|&a, &b| {
+                (dp[anterior * m + a] + grafo.distancia(a + 1, j + 1))
+                    .compara(dp[anterior * m + b] + grafo.distancia(b + 1, j + 1))
+            })
+            .unwrap();
+        s = anterior;
+        j = prox;
+    }
+
+    caminho.push(0);
+    caminho.reverse();
+    caminho
+}
+
+#[allow(dead_code)]
+pub fn solve<G>(grafo: &G, limite_memoria: usize) -> Result<Solucao<G::Peso>, ErroConstrucao>
+    where G: Instancia + Sync
+{
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let n = grafo.num_vertices();
+    if n <= 2 {
+        return Ok(Solucao::new(grafo, (0..n).collect()));
+    }
+
+    let necessaria = memoria_necessaria::<G::Peso>(n).unwrap_or(usize::max_value());
+    if necessaria > limite_memoria {
+        return Err(ErroConstrucao::MemoriaInsuficiente {
+            necessaria: necessaria,
+            limite: limite_memoria,
+        });
+    }
+
+    let m = n - 1;
+    let mut dp = vec![G::Peso::inf(); (1 << m) * m];
+
+    // Cada camada depende apenas da anterior, então seus estados são
+    // calculados em paralelo
+    for k in 1..m + 1 {
+        let mascaras = camada(m, k);
+        let mut linhas = Vec::with_capacity(mascaras.len());
+        {
+            let dp = &dp;
+            mascaras.par_iter()
+                .map(|&s| linha(grafo, dp, m, s))
+                .collect_into(&mut linhas);
+        }
+        for (&s, valores) in mascaras.iter().zip(linhas) {
+            dp[s * m..(s + 1) * m].copy_from_slice(&valores);
+        }
+    }
+
+    let solucao = Solucao::new(grafo, reconstroi(grafo, &dp, m));
+    if solucao.fo().is_inf() {
+        return Err(ErroConstrucao::Infactivel("every tour uses an INF edge".to_string()));
+    }
+    Ok(solucao)
+}
+
+// Solver exato O(2^n n²), viável até uns 25 vértices
+pub struct HeldKarp<'a, G: Instancia + Sync + 'a> {
+    grafo: &'a G,
+    limite_memoria: usize,
+}
+
+impl<'a, G: Instancia + Sync> HeldKarp<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> HeldKarp<G> {
+        HeldKarp {
+            grafo: grafo,
+            limite_memoria: LIMITE_MEMORIA_PADRAO,
+        }
+    }
+
+    // Limite, em bytes, para a tabela de programação dinâmica
+    #[allow(dead_code)]
+    pub fn limite_memoria(&mut self, limite_memoria: usize) -> &mut HeldKarp<'a, G> {
+        self.limite_memoria = limite_memoria;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<Solucao<G::Peso>, ErroConstrucao> {
+        solve(self.grafo, self.limite_memoria)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Peso};
+    use grasp::Grasp;
+    use instancia::FnInstancia;
+
+    fn otimo(grafo: &Grafo) -> Peso {
+        HeldKarp::new(grafo).solve().unwrap().fo()
+    }
+
+    #[test]
+    fn camadas() {
+        assert_eq!(camada(4, 2), vec![3, 5, 6, 9, 10, 12]);
+        assert_eq!((0..6).map(|k| camada(5, k).len()).sum::<usize>(), 32);
+    }
+
+    #[test]
+    fn otimos_conhecidos() {
+        assert_eq!(otimo(&Grafo::toy()), 8);
+        assert_eq!(otimo(&Grafo::from_arquivo("instances/p01.txt").unwrap()), 291);
+        assert_eq!(otimo(&Grafo::from_tsplib("instances/gr17.tsp").unwrap()), 2085);
+    }
+
+    #[test]
+    fn pesos_reais() {
+        let g = FnInstancia::new(9, |i, j| 0.5 * (i as f64 - j as f64).abs());
+        let s = HeldKarp::new(&g).solve().unwrap();
+        assert!(s.fo().igual(8.0));
+        assert_eq!(s.caminho()[0], 0);
+    }
+
+    #[test]
+    fn heuristicas_nao_superam_otimo() {
+        let g = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let (s, _) = Grasp::new(&g).max_iter(2).num_vizinhos(2).solve().unwrap();
+        assert!(s.fo() >= otimo(&g));
+    }
+
+    #[test]
+    fn limite_de_memoria() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        match HeldKarp::new(&g).limite_memoria(1 << 20).solve() {
+            Err(ErroConstrucao::MemoriaInsuficiente { necessaria, .. }) => {
+                assert_eq!(necessaria, (1 << 16) * 16 * 8)
+            }
+            _ => panic!("esperava erro de memória"),
+        }
+    }
+}
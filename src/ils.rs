@@ -0,0 +1,294 @@
+extern crate rand;
+
+use std::u64;
+use std::time::{Duration, Instant};
+use self::rand::{Rng, sample};
+use grafo::{Solucao, Caminho};
+use instancia::Instancia;
+use custo::Custo;
+use construcao::{self, Heuristica};
+use diagnostico;
+use busca_local::{Vnd, Vizinhanca};
+use candidatos::TipoCandidatos;
+use erro::ErroConstrucao;
+
+// Tamanho máximo do trecho embaralhado pela perturbação de trecho aleatório
+const MAX_TRECHO: usize = 8;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Perturbacao {
+    // Corta o tour em quatro partes A B C D e religa como A C B D. Nenhum
+    // 2-opt ou 3-opt sequencial desfaz o movimento.
+    DuploPonte,
+    // Embaralha um trecho de até MAX_TRECHO vértices consecutivos
+    TrechoAleatorio,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aceitacao {
+    // Segue do novo ótimo local só se ele for melhor que o atual
+    Melhor,
+    // Segue sempre do novo ótimo local
+    PasseioAleatorio,
+    // Como `Melhor`, mas reconstrói o tour depois de tantas iterações sem
+    // melhorar o atual
+    Reinicio(u64),
+}
+
+fn duplo_ponte<R: Rng>(rng: &mut R, caminho: &Caminho) -> Caminho {
+    let n = caminho.len();
+    // Cortes distintos: nenhuma das partes fica vazia
+    let mut cortes = sample(rng, 1..n, 3);
+    cortes.sort();
+    let (p1, p2, p3) = (cortes[0], cortes[1], cortes[2]);
+
+    let mut novo = Vec::with_capacity(n);
+    novo.extend(&caminho[..p1]);
+    novo.extend(&caminho[p2..p3]);
+    novo.extend(&caminho[p1..p2]);
+    novo.extend(&caminho[p3..]);
+    novo
+}
+
+fn trecho_aleatorio<R: Rng>(rng: &mut R, caminho: &Caminho) -> Caminho {
+    let n = caminho.len();
+    let tamanho = rng.gen_range(2, MAX_TRECHO.min(n) + 1);
+    let inicio = rng.gen_range(0, n - tamanho + 1);
+    let mut novo = caminho.clone();
+    rng.shuffle(&mut novo[inicio..inicio + tamanho]);
+    novo
+}
+
+fn perturba<R, G>(rng: &mut R,
+                  grafo: &G,
+                  solucao: &Solucao<G::Peso>,
+                  perturbacao: Perturbacao)
+                  -> Solucao<G::Peso>
+    where R: Rng,
+          G: Instancia
+{
+    let caminho = solucao.caminho();
+    let novo = match perturbacao {
+        // Os três cortes distintos em 1..n exigem ao menos 4 vértices
+        Perturbacao::DuploPonte if caminho.len() >= 4 => duplo_ponte(rng, caminho),
+        _ if caminho.len() >= 2 => trecho_aleatorio(rng, caminho),
+        _ => caminho.clone(),
+    };
+    Solucao::new(grafo, novo)
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           timeout: Duration,
+                           max_iter: u64,
+                           heuristica: Heuristica,
+                           perturbacao: Perturbacao,
+                           aceitacao: Aceitacao,
+                           semente: Option<&Caminho>,
+                           candidatos: Option<(TipoCandidatos, usize)>,
+                           vizinhancas: &[Vizinhanca])
+                           -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let mut rng = rand::weak_rng();
+    let t = Instant::now();
+    let vnd = Vnd::new(grafo, vizinhancas, candidatos);
+
+    let inicial = match semente {
+        Some(caminho) => Solucao::new(grafo, caminho.clone()),
+        None => construcao::constroi(&mut rng, grafo, heuristica)?,
+    };
+    let mut atual = vnd.aplica(grafo, &inicial);
+    let mut best = atual.clone();
+
+    let mut it = 0;
+    let mut it_alvo = 0;
+    let mut it_atual = 0;
+    while it - it_alvo < max_iter && t.elapsed() < timeout {
+        let perturbada = perturba(&mut rng, grafo, &atual, perturbacao);
+        let vizinho = vnd.aplica(grafo, &perturbada);
+
+        if vizinho.fo().menor(best.fo()) {
+            best = vizinho.clone();
+            it_alvo = it;
+        }
+
+        match aceitacao {
+            Aceitacao::PasseioAleatorio => atual = vizinho,
+            _ if vizinho.fo().menor(atual.fo()) => {
+                atual = vizinho;
+                it_atual = it;
+            }
+            Aceitacao::Reinicio(limite) if it - it_atual >= limite => {
+                // Com heurísticas determinísticas o tour reconstruído é o
+                // mesmo, mas as perturbações seguem outro rumo
+                let novo = construcao::constroi(&mut rng, grafo, heuristica)?;
+                atual = vnd.aplica(grafo, &novo);
+                it_atual = it;
+            }
+            _ => {}
+        }
+
+        it += 1;
+    }
+
+    Ok((best, it_alvo))
+}
+
+// Iterated Local Search: busca local, perturbação e nova busca local, a partir
+// do ótimo local aceito
+pub struct Ils<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    max_iter: u64,
+    heuristica: Heuristica,
+    perturbacao: Perturbacao,
+    aceitacao: Aceitacao,
+    semente: Option<Caminho>,
+    candidatos: Option<(TipoCandidatos, usize)>,
+    vizinhancas: Vec<Vizinhanca>,
+}
+
+impl<'a, G: Instancia> Ils<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Ils<G> {
+        Ils {
+            grafo: grafo,
+            timeout: u64::MAX,
+            max_iter: 200,
+            heuristica: Heuristica::VizinhoMaisProximo,
+            perturbacao: Perturbacao::DuploPonte,
+            aceitacao: Aceitacao::Melhor,
+            semente: None,
+            candidatos: None,
+            vizinhancas: vec![Vizinhanca::TwoOpt],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Ils<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Iterações sem melhorar a melhor solução antes de parar
+    #[allow(dead_code)]
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Ils<'a, G> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    // Construção do tour inicial e dos reinícios
+    #[allow(dead_code)]
+    pub fn heuristica(&mut self, heuristica: Heuristica) -> &mut Ils<'a, G> {
+        self.heuristica = heuristica;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn perturbacao(&mut self, perturbacao: Perturbacao) -> &mut Ils<'a, G> {
+        self.perturbacao = perturbacao;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn aceitacao(&mut self, aceitacao: Aceitacao) -> &mut Ils<'a, G> {
+        self.aceitacao = aceitacao;
+        self
+    }
+
+    // Tour inicial no lugar da construção
+    #[allow(dead_code)]
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Ils<'a, G> {
+        self.semente = Some(caminho);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn candidatos(&mut self, k: usize) -> &mut Ils<'a, G> {
+        self.candidatos = Some((TipoCandidatos::MaisProximos, k));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn candidatos_quadrantes(&mut self, k: usize) -> &mut Ils<'a, G> {
+        self.candidatos = Some((TipoCandidatos::Quadrantes, k));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn vizinhancas(&mut self, vizinhancas: Vec<Vizinhanca>) -> &mut Ils<'a, G> {
+        self.vizinhancas = vizinhancas;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.max_iter,
+              self.heuristica,
+              self.perturbacao,
+              self.aceitacao,
+              self.semente.as_ref(),
+              self.candidatos,
+              &self.vizinhancas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_permutacao, confere_qualidade};
+
+    #[test]
+    fn perturbacoes_sao_permutacoes() {
+        let mut rng = rand::weak_rng();
+        for n in 4..40 {
+            let caminho = (0..n).collect::<Vec<_>>();
+            for &perturbacao in &[Perturbacao::DuploPonte, Perturbacao::TrechoAleatorio] {
+                let novo = match perturbacao {
+                    Perturbacao::DuploPonte => duplo_ponte(&mut rng, &caminho),
+                    Perturbacao::TrechoAleatorio => trecho_aleatorio(&mut rng, &caminho),
+                };
+                if perturbacao == Perturbacao::DuploPonte {
+                    // Quatro partes não vazias: três arestas novas
+                    let trocadas = novo.windows(2).filter(|p| p[1] != p[0] + 1).count();
+                    assert_eq!(trocadas, 3, "{:?}", novo);
+                }
+                confere_permutacao(&novo, n);
+            }
+        }
+    }
+
+    #[test]
+    fn criterios_de_aceitacao() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let criterios = [Aceitacao::Melhor, Aceitacao::PasseioAleatorio, Aceitacao::Reinicio(20)];
+        for &aceitacao in &criterios {
+            let (s, _) = Ils::new(&g).max_iter(100).aceitacao(aceitacao).solve().unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 5);
+        }
+    }
+
+    #[test]
+    fn instancias_pequenas_e_semente() {
+        let g = Grafo::toy();
+        let (s, _) = Ils::new(&g).max_iter(20).solve().unwrap();
+        assert_eq!(s.fo(), 8);
+
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let semente = construcao::constroi(&mut rand::weak_rng(), &g, Heuristica::Economias)
+            .unwrap();
+        let (s, _) = Ils::new(&g)
+            .max_iter(50)
+            .semente(semente.caminho().clone())
+            .perturbacao(Perturbacao::TrechoAleatorio)
+            .vizinhancas(vec![Vizinhanca::TwoOpt, Vizinhanca::OrOpt])
+            .solve()
+            .unwrap();
+        assert!(s.fo() >= 291 && s.fo() <= semente.fo());
+    }
+}
@@ -0,0 +1,138 @@
+use std::marker::PhantomData;
+use grafo::Vertice;
+use coordenadas::Coordenadas;
+use custo::Custo;
+
+// Fonte de custos usada pelos solvers: matriz densa, coordenadas ou qualquer
+// função de distância fornecida pelo usuário
+pub trait Instancia {
+    type Peso: Custo;
+
+    fn num_vertices(&self) -> usize;
+
+    fn distancia(&self, src: Vertice, dst: Vertice) -> Self::Peso;
+
+    // Existe aresta entre os vértices (peso diferente de INF)
+    fn aresta(&self, src: Vertice, dst: Vertice) -> bool {
+        src != dst && !self.distancia(src, dst).is_inf()
+    }
+
+    fn adjacentes(&self, vertice: Vertice) -> Adjacentes<Self>
+        where Self: Sized
+    {
+        Adjacentes {
+            instancia: self,
+            vertice: vertice,
+            atual: 0,
+        }
+    }
+
+    fn coordenadas(&self) -> Option<&Coordenadas> {
+        None
+    }
+}
+
+pub struct Adjacentes<'a, G: Instancia + 'a> {
+    instancia: &'a G,
+    vertice: Vertice,
+    atual: Vertice,
+}
+
+impl<'a, G: Instancia> Iterator for Adjacentes<'a, G> {
+    type Item = (Vertice, G::Peso);
+
+    fn next(&mut self) -> Option<(Vertice, G::Peso)> {
+        if self.atual >= self.instancia.num_vertices() {
+            return None;
+        }
+        let vert = self.atual;
+        self.atual += 1;
+        Some((vert, self.instancia.distancia(self.vertice, vert)))
+    }
+}
+
+// As coordenadas sozinhas usam pesos inteiros, arredondados como na TSPLIB
+impl Instancia for Coordenadas {
+    type Peso = u64;
+
+    fn num_vertices(&self) -> usize {
+        self.pontos().len()
+    }
+
+    fn distancia(&self, src: Vertice, dst: Vertice) -> u64 {
+        if src == dst {
+            0
+        } else {
+            self.metrica().distancia(&self.pontos()[src], &self.pontos()[dst])
+        }
+    }
+
+    fn coordenadas(&self) -> Option<&Coordenadas> {
+        Some(self)
+    }
+}
+
+pub struct FnInstancia<F, P> {
+    num_vertices: usize,
+    distancia: F,
+    peso: PhantomData<P>,
+}
+
+impl<F, P> FnInstancia<F, P>
+    where F: Fn(Vertice, Vertice) -> P,
+          P: Custo
+{
+    #[allow(dead_code)]
+    pub fn new(num_vertices: usize, distancia: F) -> FnInstancia<F, P> {
+        FnInstancia {
+            num_vertices: num_vertices,
+            distancia: distancia,
+            peso: PhantomData,
+        }
+    }
+}
+
+impl<F, P> Instancia for FnInstancia<F, P>
+    where F: Fn(Vertice, Vertice) -> P,
+          P: Custo
+{
+    type Peso = P;
+
+    fn num_vertices(&self) -> usize {
+        self.num_vertices
+    }
+
+    fn distancia(&self, src: Vertice, dst: Vertice) -> P {
+        (self.distancia)(src, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Peso;
+    use grasp::Grasp;
+    use ag::Ag;
+
+    #[test]
+    fn closure_como_instancia() {
+        let posicoes = (0..12).map(|i| 3 * i as i64).collect::<Vec<_>>();
+        let g = FnInstancia::new(posicoes.len(),
+                                 |i, j| (posicoes[i] - posicoes[j]).abs() as Peso);
+        assert_eq!(g.adjacentes(3).nth(10), Some((10, 21)));
+
+        let (s, _) = Grasp::new(&g).max_iter(5).solve().unwrap();
+        assert_eq!(s.fo(), 66);
+        let (s, _) = Ag::new(&g).max_iter(5).pop_tam(20).solve().unwrap();
+        assert!(s.fo() >= 66);
+    }
+
+    #[test]
+    fn closure_pesos_reais() {
+        let g = FnInstancia::new(12, |i, j| 1.5 * (i as f64 - j as f64).abs());
+        let (s, _) = Grasp::new(&g).max_iter(5).solve().unwrap();
+        assert!(s.fo().igual(33.0));
+        let (s, _) = Ag::new(&g).max_iter(5).pop_tam(20).solve().unwrap();
+        assert!(!s.fo().menor(33.0));
+    }
+}
@@ -0,0 +1,274 @@
+use std::f64;
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+
+const ITER_SEM_MELHORA: usize = 10;
+const PASSO_MINIMO: f64 = 1e-3;
+
+// 1-árvore: árvore geradora mínima dos vértices 1..n mais as duas arestas mais
+// baratas do vértice 0, com os custos modificados pelas penalidades pi
+pub struct UmaArvore {
+    pub custo: f64,
+    pub graus: Vec<usize>,
+    pub arestas: Vec<(Vertice, Vertice)>,
+}
+
+impl UmaArvore {
+    pub fn is_tour(&self) -> bool {
+        self.graus.iter().all(|&g| g == 2)
+    }
+
+    pub fn tour(&self) -> Caminho {
+        let n = self.graus.len();
+        let mut vizinhos = vec![vec![]; n];
+        for &(i, j) in &self.arestas {
+            vizinhos[i].push(j);
+            vizinhos[j].push(i);
+        }
+
+        let mut caminho = vec![0];
+        let mut anterior = 0;
+        let mut atual = vizinhos[0][0];
+        while atual != 0 {
+            caminho.push(atual);
+            let prox = if vizinhos[atual][0] == anterior {
+                vizinhos[atual][1]
+            } else {
+                vizinhos[atual][0]
+            };
+            anterior = atual;
+            atual = prox;
+        }
+        caminho
+    }
+}
+
+// 1-árvore mínima de `n` >= 3 vértices. `custo` dá o peso de cada aresta e
+// `chave` a ordem de escolha, o que permite forçar arestas.
+pub fn uma_arvore<C, K>(n: usize, pi: &[f64], custo: C, chave: K) -> UmaArvore
+    where C: Fn(Vertice, Vertice) -> f64,
+          K: Fn(Vertice, Vertice) -> f64
+{
+    let mut arvore = UmaArvore {
+        custo: 0.0,
+        graus: vec![0; n],
+        arestas: Vec::with_capacity(n),
+    };
+
+    // Prim nos vértices 1..n
+    let mut na_arvore = vec![false; n];
+    let mut menor = vec![f64::INFINITY; n];
+    let mut pai = vec![1; n];
+    na_arvore[0] = true;
+    menor[1] = 0.0;
+    for _ in 1..n {
+        let u = (1..n)
+            .filter(|&v| !na_arvore[v])
+            .min_by(|&a, &b| menor[a].partial_cmp(&menor[b]).unwrap())
+            .unwrap();
+        na_arvore[u] = true;
+        if u != 1 {
+            arvore.arestas.push((pai[u], u));
+        }
+        for v in 1..n {
+            if !na_arvore[v] {
+                let c = chave(u, v) + pi[u] + pi[v];
+                if c < menor[v] {
+                    menor[v] = c;
+                    pai[v] = u;
+                }
+            }
+        }
+    }
+
+    let mut candidatos = (1..n).collect::<Vec<_>>();
+    candidatos.sort_by(|&a, &b| {
+        (chave(0, a) + pi[a]).partial_cmp(&(chave(0, b) + pi[b])).unwrap()
+    });
+    arvore.arestas.push((0, candidatos[0]));
+    arvore.arestas.push((0, candidatos[1]));
+
+    arvore.custo = -2.0 * pi.iter().sum::<f64>();
+    for &(i, j) in &arvore.arestas {
+        arvore.custo += custo(i, j) + pi[i] + pi[j];
+        arvore.graus[i] += 1;
+        arvore.graus[j] += 1;
+    }
+    arvore
+}
+
+// Otimização por subgradiente das penalidades pi, com o passo de Held, Wolfe e
+// Crowder. Devolve a 1-árvore de maior custo, deixando em `pi` as penalidades
+// correspondentes; para antes se `suficiente` aceitar o limite.
+pub fn subgradiente<F, S>(pi: &mut Vec<f64>,
+                          iteracoes: usize,
+                          limite_superior: f64,
+                          uma_arvore: F,
+                          suficiente: S)
+                          -> UmaArvore
+    where F: Fn(&[f64]) -> UmaArvore,
+          S: Fn(f64) -> bool
+{
+    let mut atual = pi.clone();
+    let mut melhor: Option<UmaArvore> = None;
+    let mut passo = 2.0;
+    let mut sem_melhora = 0;
+
+    for _ in 0..iteracoes.max(1) {
+        let arvore = uma_arvore(&atual);
+        if arvore.is_tour() || suficiente(arvore.custo) {
+            pi.clone_from(&atual);
+            return arvore;
+        }
+
+        let direcao = arvore.graus.iter().map(|&g| g as f64 - 2.0).collect::<Vec<_>>();
+        let norma = direcao.iter().map(|d| d * d).sum::<f64>();
+        let t = passo * (limite_superior - arvore.custo) / norma;
+        if melhor.as_ref().map_or(true, |m| arvore.custo > m.custo) {
+            pi.clone_from(&atual);
+            melhor = Some(arvore);
+            sem_melhora = 0;
+        } else {
+            sem_melhora += 1;
+            if sem_melhora >= ITER_SEM_MELHORA {
+                passo /= 2.0;
+                sem_melhora = 0;
+            }
+        }
+        if passo < PASSO_MINIMO {
+            break;
+        }
+
+        for (p, d) in atual.iter_mut().zip(direcao) {
+            *p += t * d;
+        }
+    }
+
+    melhor.unwrap()
+}
+
+// Distância relativa entre o custo de um tour e um limite inferior
+pub fn gap<P: Custo>(fo: P, limite: P) -> f64 {
+    let lb = limite.to_f64();
+    if lb <= 0.0 {
+        return 0.0;
+    }
+    (fo.to_f64() - lb) / lb
+}
+
+#[allow(dead_code)]
+pub fn calcula<G: Instancia>(grafo: &G,
+                             iteracoes: usize,
+                             limite_superior: Option<G::Peso>)
+                             -> G::Peso {
+    let n = grafo.num_vertices();
+    let caminho = (0..n).collect::<Vec<_>>();
+    let ub = limite_superior.unwrap_or_else(|| Solucao::new(grafo, caminho).fo());
+    if n < 3 {
+        return ub;
+    }
+
+    // Nas instâncias assimétricas, o menor peso entre as duas direções ainda
+    // dá um limite válido
+    let custos = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| {
+                    let (a, b) = (grafo.distancia(i, j), grafo.distancia(j, i));
+                    if b.menor(a) { b.to_f64() } else { a.to_f64() }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let custo = |i: Vertice, j: Vertice| custos[i][j];
+
+    let arvore = subgradiente(&mut vec![0.0; n],
+                              iteracoes,
+                              ub.to_f64(),
+                              |pi| uma_arvore(n, pi, &custo, &custo),
+                              |lb| !G::Peso::limite_inferior(lb).menor(ub));
+    G::Peso::limite_inferior(arvore.custo)
+}
+
+// Limite inferior de Held-Karp para o custo de qualquer tour da instância
+pub struct LimiteInferior<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    iteracoes: usize,
+    limite_superior: Option<G::Peso>,
+}
+
+impl<'a, G: Instancia> LimiteInferior<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> LimiteInferior<G> {
+        LimiteInferior {
+            grafo: grafo,
+            iteracoes: 1000,
+            limite_superior: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn iteracoes(&mut self, iteracoes: usize) -> &mut LimiteInferior<'a, G> {
+        self.iteracoes = iteracoes;
+        self
+    }
+
+    // Custo de um tour conhecido, usado no tamanho do passo. Sem ele, usa o
+    // caminho 0, 1, ..., n - 1.
+    #[allow(dead_code)]
+    pub fn limite_superior(&mut self, limite_superior: G::Peso) -> &mut LimiteInferior<'a, G> {
+        self.limite_superior = Some(limite_superior);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn calcula(&self) -> G::Peso {
+        calcula(self.grafo, self.iteracoes, self.limite_superior)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::{Grafo, Peso};
+
+    fn limite(arquivo: &str, otimo: Peso) -> Peso {
+        let g: Grafo = Grafo::from_tsplib(arquivo).unwrap();
+        LimiteInferior::new(&g).limite_superior(otimo).calcula()
+    }
+
+    #[test]
+    fn limites_proximos_do_otimo() {
+        for &(arquivo, otimo) in &[("instances/gr17.tsp", 2085),
+                                   ("instances/fri26.tsp", 937),
+                                   ("instances/dantzig42.tsp", 699),
+                                   ("instances/att48.tsp", 10628)] {
+            let lb = limite(arquivo, otimo);
+            assert!(lb <= otimo);
+            assert!(gap(otimo, lb) < 0.01);
+        }
+    }
+
+    #[test]
+    fn sem_limite_superior() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let lb = LimiteInferior::new(&g).calcula();
+        assert!(lb <= 291 && lb > 250);
+    }
+
+    #[test]
+    fn assimetrica() {
+        let g = Grafo::from_matriz(vec![vec![0, 1, 9, 9],
+                                        vec![9, 0, 1, 9],
+                                        vec![9, 9, 0, 1],
+                                        vec![1, 9, 9, 0]]);
+        assert!(LimiteInferior::new(&g).calcula() <= 4);
+    }
+
+    #[test]
+    fn calculo_do_gap() {
+        assert!((gap(110, 100) - 0.1).abs() < 1e-12);
+        assert_eq!(gap(5, 0), 0.0);
+    }
+}
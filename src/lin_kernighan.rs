@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use grafo::{Solucao, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use candidatos::Candidatos;
+use busca_local::Tour;
+
+// Profundidade máxima de um movimento, em trocas de aresta
+const PROFUNDIDADE_MAX: usize = 50;
+
+// Alternativas tentadas em cada nível antes de desistir do movimento. Depois
+// dos dois primeiros níveis a busca é gulosa, sem backtracking.
+const LARGURA: [usize; 2] = [5, 3];
+
+// Movimento de profundidade variável a partir de t1: cada passo quebra a
+// aresta (t1, t2), liga t2 a t3 e troca (t3, t4) por (t4, t1), que é o 2-opt
+// que mantém o tour fechado. A cadeia segue enquanto a soma parcial dos ganhos
+// é positiva, e no fim volta ao ponto em que o tour ficou mais curto.
+struct Lk<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    candidatos: &'a Candidatos,
+    tour: Tour,
+    fo: G::Peso,
+    melhor_fo: G::Peso,
+    // (t1, t2, t4) de cada passo aplicado, para desfazê-los
+    passos: Vec<(Vertice, Vertice, Vertice)>,
+    melhor_passos: usize,
+    removidas: Vec<(Vertice, Vertice)>,
+    adicionadas: Vec<(Vertice, Vertice)>,
+}
+
+fn contem(arestas: &[(Vertice, Vertice)], a: Vertice, b: Vertice) -> bool {
+    arestas.iter().any(|&(i, j)| (i, j) == (a, b) || (i, j) == (b, a))
+}
+
+impl<'a, G: Instancia> Lk<'a, G> {
+    // Troca (t1, t2) e (t3, t4) por (t2, t3) e (t4, t1)
+    fn troca(&mut self, t1: Vertice, t2: Vertice, t4: Vertice) {
+        if self.tour.sucessor(t1) == t2 {
+            self.tour.inverte(t2, t4);
+        } else {
+            self.tour.inverte(t4, t2);
+        }
+    }
+
+    fn desfaz_ultimo(&mut self) {
+        let (t1, t2, t4) = self.passos.pop().unwrap();
+        // Agora (t1, t4) está no tour: a mesma troca, com t4 no lugar de t2,
+        // devolve as arestas originais
+        self.troca(t1, t4, t2);
+        self.removidas.pop();
+        self.adicionadas.pop();
+    }
+
+    // Escolhas de (t3, t4) que respeitam o critério de ganho, da mais
+    // promissora para a menos: maior d(t3, t4) - d(t2, t3)
+    fn alternativas(&self,
+                    t1: Vertice,
+                    t2: Vertice,
+                    removidas: G::Peso,
+                    adicionadas: G::Peso)
+                    -> Vec<(Vertice, Vertice)> {
+        let sucessor = self.tour.sucessor(t1) == t2;
+        let vizinhos = (self.tour.sucessor(t2), self.tour.antecessor(t2));
+        let mut opcoes = vec![];
+        for &t3 in &self.candidatos[t2] {
+            let d_23 = self.grafo.distancia(t2, t3);
+            if !(adicionadas + d_23).menor(removidas) {
+                break;
+            }
+            if t3 == vizinhos.0 || t3 == vizinhos.1 || contem(&self.removidas, t2, t3) {
+                continue;
+            }
+            let t4 = if sucessor { self.tour.antecessor(t3) } else { self.tour.sucessor(t3) };
+            if contem(&self.adicionadas, t3, t4) {
+                continue;
+            }
+            let prioridade = self.grafo.distancia(t3, t4).to_f64() - d_23.to_f64();
+            opcoes.push((t3, t4, prioridade));
+        }
+        opcoes.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        opcoes.into_iter().map(|(t3, t4, _)| (t3, t4)).collect()
+    }
+
+    // Estende a cadeia a partir da ponta t2. Devolve true se algum ponto dela
+    // melhorou o tour; nesse caso os passos ficam aplicados.
+    fn aprofunda(&mut self,
+                 t1: Vertice,
+                 t2: Vertice,
+                 nivel: usize,
+                 removidas: G::Peso,
+                 adicionadas: G::Peso)
+                 -> bool {
+        if nivel >= PROFUNDIDADE_MAX {
+            return false;
+        }
+        let largura = LARGURA.get(nivel).cloned().unwrap_or(1);
+        let alternativas = self.alternativas(t1, t2, removidas, adicionadas);
+        for (t3, t4) in alternativas.into_iter().take(largura) {
+            let removidas = removidas + self.grafo.distancia(t3, t4);
+            let adicionadas = adicionadas + self.grafo.distancia(t2, t3);
+            self.troca(t1, t2, t4);
+            self.passos.push((t1, t2, t4));
+            self.removidas.push((t3, t4));
+            self.adicionadas.push((t2, t3));
+
+            let fechamento = adicionadas + self.grafo.distancia(t4, t1);
+            if fechamento.menor(removidas) {
+                let fo = self.fo + fechamento - removidas;
+                if fo.menor(self.melhor_fo) {
+                    self.melhor_fo = fo;
+                    self.melhor_passos = self.passos.len();
+                }
+            }
+            if self.aprofunda(t1, t4, nivel + 1, removidas, adicionadas) ||
+               self.melhor_passos > 0 {
+                return true;
+            }
+            self.desfaz_ultimo();
+        }
+        false
+    }
+
+    // Tenta um movimento a partir de t1. Devolve os vértices cujas arestas
+    // mudaram, se o tour melhorou.
+    fn melhora(&mut self, t1: Vertice) -> Option<Vec<Vertice>> {
+        for &sucessor in &[true, false] {
+            let t2 = if sucessor { self.tour.sucessor(t1) } else { self.tour.antecessor(t1) };
+            let d_12 = self.grafo.distancia(t1, t2);
+            self.removidas = vec![(t1, t2)];
+            self.adicionadas = vec![];
+            self.melhor_fo = self.fo;
+            self.melhor_passos = 0;
+
+            if self.aprofunda(t1, t2, 0, d_12, G::Peso::zero()) {
+                while self.passos.len() > self.melhor_passos {
+                    self.desfaz_ultimo();
+                }
+                self.fo = self.melhor_fo;
+                let mut afetados = vec![t1, t2];
+                for (&(_, b, _), &(c, d)) in self.passos.iter().zip(&self.removidas[1..]) {
+                    afetados.extend(&[b, c, d]);
+                }
+                self.passos.clear();
+                return Some(afetados);
+            }
+        }
+        None
+    }
+}
+
+// Busca local de Lin-Kernighan com don't-look bits, restrita às listas de
+// candidatos. Supõe pesos simétricos.
+pub fn lin_kernighan<G: Instancia>(grafo: &G,
+                                   solucao: &Solucao<G::Peso>,
+                                   candidatos: &Candidatos)
+                                   -> Solucao<G::Peso> {
+    let n = solucao.caminho().len();
+    if n < 5 {
+        return solucao.clone();
+    }
+    let mut lk = Lk {
+        grafo: grafo,
+        candidatos: candidatos,
+        tour: Tour::new(solucao.caminho()),
+        fo: solucao.fo(),
+        melhor_fo: solucao.fo(),
+        passos: vec![],
+        melhor_passos: 0,
+        removidas: vec![],
+        adicionadas: vec![],
+    };
+    let mut na_fila = vec![true; n];
+    let mut fila = solucao.caminho().iter().cloned().collect::<VecDeque<_>>();
+
+    while let Some(t1) = fila.pop_front() {
+        na_fila[t1] = false;
+        if let Some(afetados) = lk.melhora(t1) {
+            for v in afetados {
+                if !na_fila[v] {
+                    na_fila[v] = true;
+                    fila.push_back(v);
+                }
+            }
+        }
+    }
+
+    Solucao::com_fo(lk.tour.caminho(), lk.fo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_fo, confere_permutacao, confere_qualidade, pontos_aleatorios,
+                 solucao_aleatoria};
+    use busca_local::{self, Vizinhanca};
+    use grasp::Grasp;
+    use ag::Ag;
+
+    #[test]
+    fn fo_incremental_correta() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let candidatos = g.candidatos(8);
+        for _ in 0..10 {
+            let s = lin_kernighan(&g, &solucao_aleatoria(&g), &candidatos);
+            confere_permutacao(s.caminho(), 48);
+            confere_qualidade(&g, &s, OTIMO_ATT48, 5);
+        }
+    }
+
+    // Em instâncias pequenas os dois chegam perto do ótimo; a diferença
+    // aparece com algumas centenas de vértices
+    #[test]
+    fn melhor_que_3opt() {
+        let g = pontos_aleatorios(500);
+        let candidatos = g.candidatos(8);
+        let (mut lk, mut three_opt) = (0, 0);
+        for _ in 0..3 {
+            let inicial = busca_local::two_opt(&g, &solucao_aleatoria(&g), &candidatos);
+            let s = lin_kernighan(&g, &inicial, &candidatos);
+            confere_fo(&g, &s);
+            lk += s.fo();
+            three_opt += busca_local::three_opt(&g, &inicial, &candidatos, true).fo();
+        }
+        assert!(lk < three_opt);
+    }
+
+    #[test]
+    fn opcao_dos_solvers() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let (s, _) = Grasp::new(&g)
+            .max_iter(2)
+            .num_vizinhos(2)
+            .vizinhancas(vec![Vizinhanca::LinKernighan])
+            .solve()
+            .unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 3);
+
+        let (s, _) = Ag::new(&g)
+            .max_iter(5)
+            .pop_tam(20)
+            .pos_otimizacao(vec![Vizinhanca::LinKernighan])
+            .solve()
+            .unwrap();
+        confere_qualidade(&g, &s, OTIMO_ATT48, 5);
+    }
+}
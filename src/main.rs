@@ -3,57 +3,277 @@
 
 #![allow(ptr_arg)]
 
+extern crate rand;
+
 mod grasp;
 mod grafo;
 mod ag;
+mod tsplib;
+mod erro;
+mod diagnostico;
+mod construcao;
+mod coordenadas;
+mod instancia;
+mod custo;
+mod held_karp;
+mod branch_bound;
+mod limite_inferior;
+mod emparelhamento;
+mod christofides;
+mod candidatos;
+mod busca_local;
+mod lin_kernighan;
+mod ils;
+mod recozimento;
+mod tabu;
+mod aco;
+mod vizinhanca;
+mod vns;
+mod eax;
+#[cfg(test)]
+mod testes;
 
 use std::env;
 use std::process;
+use std::path::Path;
 use std::time::Instant;
-use grafo::{Grafo, INF};
+use grafo::{Grafo, Solucao, Caminho, INF};
 use grasp::Grasp;
 use ag::Ag;
+use ils::Ils;
+use recozimento::Recozimento;
+use tabu::Tabu;
+use aco::Aco;
+use vns::Vns;
+use held_karp::HeldKarp;
+use branch_bound::BranchBound;
+use instancia::Instancia;
+use limite_inferior::LimiteInferior;
+use construcao::Heuristica;
+use erro::ErroConstrucao;
 use std::cell::RefCell;
 use std::collections::HashMap;
 
+fn aborta(e: ErroConstrucao) -> ! {
+    println!("Erro: {}", e);
+    process::exit(1);
+}
+
+// Acima disso o subgradiente (1-árvore O(n²) por iteração) demora mais que os
+// próprios solvers, e o gap não é calculado
+const MAX_VERTICES_GAP: usize = 1000;
+
+fn imprime_gap(grafo: &Grafo, solucao: &Solucao) {
+    if grafo.num_vertices() > MAX_VERTICES_GAP {
+        println!("Gap: não calculado (mais de {} vértices)", MAX_VERTICES_GAP);
+        return;
+    }
+    let lb = LimiteInferior::new(grafo).limite_superior(solucao.fo()).calcula();
+    println!("Limite inferior: {}", lb);
+    println!("Gap: {:.2}%", 100.0 * limite_inferior::gap(solucao.fo(), lb));
+}
+
 #[allow(dead_code)]
-fn teste_grasp(grafo: &Grafo) {
+fn teste_grasp(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
     println!("Grasp");
     let t = Instant::now();
-    let (solucao, it) = Grasp::new(grafo).max_iter(40).timeout(INF).solve();
+    let mut grasp = Grasp::new(grafo);
+    grasp.max_iter(40).timeout(INF);
+    if let Some(caminho) = semente {
+        grasp.semente(caminho);
+    }
+    let (solucao, it) = grasp.solve().unwrap_or_else(|e| aborta(e));
     let tempo = t.elapsed();
 
     println!("Caminho: {:?}", solucao.caminho());
     println!("Iteração alvo: {}", it);
     println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
     println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
     println!("-------------------\n");
+    solucao
 }
 
 #[allow(dead_code)]
-fn teste_ag(grafo: &Grafo) {
+fn teste_ag(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
     println!("AG");
     let t = Instant::now();
-    let (solucao, it) = Ag::new(grafo)
-        .max_iter(INF)
-        .timeout(5)
-        .mut_chance(0.3)
-        .pop_tam(1000)
-        .xo_chance(1.0)
-        .solve();
+    let mut ag = Ag::new(grafo);
+    ag.max_iter(INF).timeout(5).mut_chance(0.3).pop_tam(1000).xo_chance(1.0);
+    if let Some(caminho) = semente {
+        ag.semente(caminho);
+    }
+    let (solucao, it) = ag.solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Iteração alvo: {}", it);
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_ils(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
+    println!("ILS");
+    let t = Instant::now();
+    let mut ils = Ils::new(grafo);
+    ils.max_iter(200).timeout(INF);
+    if let Some(caminho) = semente {
+        ils.semente(caminho);
+    }
+    let (solucao, it) = ils.solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Iteração alvo: {}", it);
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_recozimento(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
+    println!("Recozimento simulado");
+    let t = Instant::now();
+    let mut recozimento = Recozimento::new(grafo);
+    recozimento.timeout(5);
+    if let Some(caminho) = semente {
+        recozimento.semente(caminho);
+    }
+    let (solucao, it) = recozimento.solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Iteração alvo: {}", it);
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_tabu(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
+    println!("Busca tabu");
+    let t = Instant::now();
+    let mut tabu = Tabu::new(grafo);
+    tabu.max_iter(200).timeout(INF);
+    if let Some(caminho) = semente {
+        tabu.semente(caminho);
+    }
+    let (solucao, it) = tabu.solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Iteração alvo: {}", it);
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_vns(grafo: &Grafo, semente: Option<Caminho>) -> Solucao {
+    println!("VNS");
+    let t = Instant::now();
+    let mut vns = Vns::new(grafo);
+    vns.max_iter(50).timeout(INF);
+    if let Some(caminho) = semente {
+        vns.semente(caminho);
+    }
+    let (solucao, it) = vns.solve().unwrap_or_else(|e| aborta(e));
     let tempo = t.elapsed();
 
     println!("Caminho: {:?}", solucao.caminho());
     println!("Iteração alvo: {}", it);
     println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
     println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
     println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_aco(grafo: &Grafo) -> Solucao {
+    println!("Colônia de formigas");
+    let t = Instant::now();
+    let (solucao, it) = Aco::new(grafo)
+        .max_iter(100)
+        .timeout(INF)
+        .solve()
+        .unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Iteração alvo: {}", it);
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+fn teste_construcao(grafo: &Grafo, heuristica: Heuristica) -> Solucao {
+    println!("Construção: {:?}", heuristica);
+    let t = Instant::now();
+    let solucao = construcao::constroi(&mut rand::weak_rng(), grafo, heuristica)
+        .unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Fo: {}", solucao.fo());
+    imprime_gap(grafo, &solucao);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_held_karp(grafo: &Grafo) -> Solucao {
+    println!("Held-Karp");
+    let t = Instant::now();
+    let solucao = HeldKarp::new(grafo).solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", solucao.caminho());
+    println!("Fo: {}", solucao.fo());
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    solucao
+}
+
+#[allow(dead_code)]
+fn teste_branch_bound(grafo: &Grafo) -> Solucao {
+    println!("Branch-and-bound");
+    let t = Instant::now();
+    let resultado = BranchBound::new(grafo).timeout(60).solve().unwrap_or_else(|e| aborta(e));
+    let tempo = t.elapsed();
+
+    println!("Caminho: {:?}", resultado.solucao.caminho());
+    println!("Fo: {}", resultado.solucao.fo());
+    if resultado.otimo {
+        println!("Ótimo provado");
+    } else {
+        println!("Limite inferior: {} (gap {:.2}%)",
+                 resultado.limite_inferior,
+                 100.0 * resultado.gap());
+    }
+    println!("Nós: {}", resultado.nos);
+    println!("Tempo: {}.{}", tempo.as_secs(), tempo.subsec_nanos());
+    println!("-------------------\n");
+    resultado.solucao
 }
 
 #[allow(dead_code)]
 fn bfs_run() {
     println!("Digite o tamanho da matriz, seguido por ela: ");
-    let g = Grafo::from_stdin();
+    let g: Grafo = Grafo::from_stdin().expect("Matriz inválida");
     let dist = grafo::bfs_distancia(&g, 3);
     println!("{:?}", dist);
 }
@@ -108,19 +328,71 @@ fn teste() {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    let heuristica = match args.iter().position(|a| a == "-c") {
+        Some(i) if i + 1 < args.len() => {
+            let nome = args.remove(i + 1);
+            args.remove(i);
+            match Heuristica::from_nome(&nome) {
+                Some(h) => Some(h),
+                None => {
+                    println!("Construção desconhecida: {}", nome);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(_) => {
+            println!("Opções inválidas");
+            process::exit(1);
+        }
+        None => None,
+    };
 
-    let grafo: Grafo = match args.len() {
-        1 => Grafo::toy(),
-        2 => Grafo::from_arquivo(&args[1]),
+    let grafo = match args.len() {
+        1 => Ok(Grafo::toy()),
+        2 | 3 if args[1].ends_with(".tsp") => Grafo::from_tsplib(&args[1]),
+        2 | 3 => Grafo::from_arquivo(&args[1]),
         _ => {
             println!("Opções inválidas");
             process::exit(1);
         }
     };
 
-    teste_ag(&grafo);
-    // teste_grasp(&grafo);
+    let grafo = match grafo {
+        Ok(grafo) => grafo,
+        Err(e) => {
+            println!("Erro ao carregar a instância {}: {}", args[1], e);
+            process::exit(1);
+        }
+    };
+
+    let diagnostico = grafo.diagnostico();
+    println!("Diagnóstico da instância");
+    println!("{}", diagnostico);
+    if heuristica == Some(Heuristica::Christofides) && !diagnostico.metrica() {
+        println!("Aviso: instância não métrica (ou não verificada); o tour de Christofides \
+                  perde a garantia de 1,5 vez o ótimo\n");
+    }
+
+    let semente = heuristica.map(|h| teste_construcao(&grafo, h).caminho().clone());
+    let solucao = teste_ag(&grafo, semente);
+    // teste_grasp(&grafo, None);
+    // teste_ils(&grafo, None);
+    // teste_recozimento(&grafo, None);
+    // teste_tabu(&grafo, None);
+    // teste_vns(&grafo, None);
+    // teste_aco(&grafo);
+    // teste_held_karp(&grafo);
+    // teste_branch_bound(&grafo);
     // bfs_run();
     // teste();
-}
\ No newline at end of file
+
+    if let Some(saida) = args.get(2) {
+        let nome = Path::new(&args[1]).file_stem().and_then(|s| s.to_str()).unwrap_or("toy");
+        if let Err(e) = tsplib::salva_tour(saida, nome, &solucao) {
+            println!("Erro ao salvar o tour: {}", e);
+            process::exit(1);
+        }
+    }
+}
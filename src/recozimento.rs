@@ -0,0 +1,456 @@
+extern crate rand;
+
+use std::u64;
+use std::time::{Duration, Instant};
+use self::rand::Rng;
+use grafo::{Solucao, Caminho};
+use instancia::Instancia;
+use custo::Custo;
+use construcao::{self, Heuristica};
+use diagnostico;
+use erro::ErroConstrucao;
+
+// Movimentos sorteados, sem aplicar, para estimar a temperatura inicial
+const AMOSTRAS: usize = 200;
+
+// Movimentos por patamar de temperatura, por vértice, quando não configurado
+const PATAMAR_POR_VERTICE: usize = 10;
+
+// Fração de movimentos aceitos num patamar abaixo da qual a busca é
+// considerada congelada
+const TAXA_MINIMA: f64 = 0.01;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resfriamento {
+    // T = αT ao fim de cada patamar
+    Geometrico(f64),
+    // T = T / (1 + βT) a cada movimento: depois de k movimentos, T fica
+    // perto de 1 / (βk)
+    LundyMees(f64),
+    // Geométrico, mas quando a busca congela sem melhorar a melhor solução,
+    // volta a uma temperatura acima daquela em que ela foi encontrada, mais
+    // alta a cada reaquecimento sem sucesso
+    Reaquecimento(f64),
+}
+
+enum Movimento {
+    // Inverte caminho[i + 1..j + 1]
+    TwoOpt(usize, usize),
+    // Move o trecho de `tamanho` vértices que começa em `inicio` para depois
+    // do vértice `destino` do restante do tour, na ordem que começa logo
+    // após o trecho
+    OrOpt {
+        inicio: usize,
+        tamanho: usize,
+        destino: usize,
+        invertido: bool,
+    },
+}
+
+// 2-opt entre posições não adjacentes; só vale para pesos simétricos
+fn sorteia_2opt<R, G>(rng: &mut R, grafo: &G, caminho: &Caminho) -> (Movimento, G::Peso, G::Peso)
+    where R: Rng,
+          G: Instancia
+{
+    let n = caminho.len();
+    loop {
+        let (a, b) = (rng.gen_range(0, n), rng.gen_range(0, n));
+        let (i, j) = if a < b { (a, b) } else { (b, a) };
+        if j < i + 2 || (i == 0 && j == n - 1) {
+            continue;
+        }
+        let (vi, vi1, vj, vj1) = (caminho[i], caminho[i + 1], caminho[j], caminho[(j + 1) % n]);
+        let adicionadas = grafo.distancia(vi, vj) + grafo.distancia(vi1, vj1);
+        let removidas = grafo.distancia(vi, vi1) + grafo.distancia(vj, vj1);
+        return (Movimento::TwoOpt(i, j), adicionadas, removidas);
+    }
+}
+
+// Or-opt de 1 a 3 vértices; a inversão do trecho entra no custo, então também
+// vale para instâncias assimétricas
+fn sorteia_or_opt<R, G>(rng: &mut R, grafo: &G, caminho: &Caminho) -> (Movimento, G::Peso, G::Peso)
+    where R: Rng,
+          G: Instancia
+{
+    let n = caminho.len();
+    let tamanho = rng.gen_range(1, 4);
+    let inicio = rng.gen_range(0, n);
+    // O último vértice do restante é o anterior ao trecho: inserir depois
+    // dele não muda nada
+    let destino = rng.gen_range(0, n - tamanho - 1);
+    let invertido = tamanho > 1 && rng.gen();
+
+    let v = |t: usize| caminho[(inicio + t) % n];
+    let (primeiro, ultimo) = (v(0), v(tamanho - 1));
+    let (antes, depois) = (v(n - 1), v(tamanho));
+    let (x, y) = (v(tamanho + destino), v(tamanho + destino + 1));
+
+    let mut adicionadas = grafo.distancia(antes, depois);
+    let mut removidas = grafo.distancia(antes, primeiro) + grafo.distancia(ultimo, depois) +
+                        grafo.distancia(x, y);
+    if invertido {
+        for t in 0..tamanho - 1 {
+            removidas = removidas + grafo.distancia(v(t), v(t + 1));
+            adicionadas = adicionadas + grafo.distancia(v(t + 1), v(t));
+        }
+        adicionadas = adicionadas + grafo.distancia(x, ultimo) + grafo.distancia(primeiro, y);
+    } else {
+        adicionadas = adicionadas + grafo.distancia(x, primeiro) + grafo.distancia(ultimo, y);
+    }
+
+    let movimento = Movimento::OrOpt {
+        inicio: inicio,
+        tamanho: tamanho,
+        destino: destino,
+        invertido: invertido,
+    };
+    (movimento, adicionadas, removidas)
+}
+
+fn aplica(caminho: &mut Caminho, movimento: &Movimento) {
+    match *movimento {
+        Movimento::TwoOpt(i, j) => caminho[i + 1..j + 1].reverse(),
+        Movimento::OrOpt { inicio, tamanho, destino, invertido } => {
+            let n = caminho.len();
+            let mut trecho = (0..tamanho).map(|t| caminho[(inicio + t) % n]).collect::<Vec<_>>();
+            if invertido {
+                trecho.reverse();
+            }
+            let mut novo = Vec::with_capacity(n);
+            for t in tamanho..n {
+                novo.push(caminho[(inicio + t) % n]);
+                if t == tamanho + destino {
+                    novo.extend(&trecho);
+                }
+            }
+            *caminho = novo;
+        }
+    }
+}
+
+fn sorteia<R, G>(rng: &mut R,
+                 grafo: &G,
+                 caminho: &Caminho,
+                 simetrica: bool)
+                 -> (Movimento, G::Peso, G::Peso)
+    where R: Rng,
+          G: Instancia
+{
+    if simetrica && rng.gen() {
+        sorteia_2opt(rng, grafo, caminho)
+    } else {
+        sorteia_or_opt(rng, grafo, caminho)
+    }
+}
+
+// Temperatura em que um movimento de piora média é aceito com a probabilidade
+// dada
+fn temperatura_inicial<R, G>(rng: &mut R,
+                             grafo: &G,
+                             caminho: &Caminho,
+                             simetrica: bool,
+                             aceitacao: f64)
+                             -> f64
+    where R: Rng,
+          G: Instancia
+{
+    let pioras = (0..AMOSTRAS)
+        .map(|_| sorteia(rng, grafo, caminho, simetrica))
+        .map(|(_, adicionadas, removidas)| adicionadas.to_f64() - removidas.to_f64())
+        .filter(|&delta| delta > 0.0 && delta < G::Peso::inf().to_f64())
+        .collect::<Vec<_>>();
+    if pioras.is_empty() {
+        return 1.0;
+    }
+    let media = pioras.iter().sum::<f64>() / pioras.len() as f64;
+    -media / aceitacao.ln()
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           timeout: Duration,
+                           max_iter: u64,
+                           heuristica: Heuristica,
+                           semente: Option<&Caminho>,
+                           resfriamento: Resfriamento,
+                           temperatura: Option<f64>,
+                           aceitacao_inicial: f64,
+                           patamar: Option<usize>)
+                           -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    // Fora de (0, 1), ln(aceitação) não é negativo e a temperatura inicial
+    // fica infinita ou negativa; com temperatura explícita ela não é usada
+    if temperatura.is_none() && !(aceitacao_inicial > 0.0 && aceitacao_inicial < 1.0) {
+        let motivo = format!("initial acceptance must be in (0, 1), got {}", aceitacao_inicial);
+        return Err(ErroConstrucao::ParametroInvalido(motivo));
+    }
+    // α fora de (0, 1) não resfria, e β não positivo aquece ou congela a
+    // temperatura
+    match resfriamento {
+        Resfriamento::Geometrico(alfa) |
+        Resfriamento::Reaquecimento(alfa) if !(alfa > 0.0 && alfa < 1.0) => {
+            let motivo = format!("cooling factor must be in (0, 1), got {}", alfa);
+            return Err(ErroConstrucao::ParametroInvalido(motivo));
+        }
+        Resfriamento::LundyMees(beta) if !(beta > 0.0) => {
+            let motivo = format!("Lundy-Mees beta must be positive, got {}", beta);
+            return Err(ErroConstrucao::ParametroInvalido(motivo));
+        }
+        _ => {}
+    }
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let mut rng = rand::weak_rng();
+    let t = Instant::now();
+
+    let inicial = match semente {
+        Some(caminho) => Solucao::new(grafo, caminho.clone()),
+        None => construcao::constroi(&mut rng, grafo, heuristica)?,
+    };
+    let n = inicial.caminho().len();
+    // Or-opt precisa de espaço para o trecho, seus vizinhos e o destino
+    if n < 5 {
+        return Ok((inicial, 0));
+    }
+
+    let simetrica = diagnostico::simetrica(grafo);
+    let mut caminho = inicial.caminho().clone();
+    let mut fo = inicial.fo();
+    let mut best = inicial;
+    let mut temperatura = temperatura.unwrap_or_else(|| {
+        temperatura_inicial(&mut rng, grafo, &caminho, simetrica, aceitacao_inicial)
+    });
+    let temperatura_maxima = temperatura;
+    let mut temperatura_melhor = temperatura;
+    let mut reaquecimentos = 0;
+    let patamar = patamar.unwrap_or(PATAMAR_POR_VERTICE * n);
+
+    let mut it = 0;
+    let mut it_alvo = 0;
+    while it < max_iter && t.elapsed() < timeout {
+        let mut aceitos = 0;
+        let mut melhorou = false;
+
+        for _ in 0..patamar {
+            let (movimento, adicionadas, removidas) =
+                sorteia(&mut rng, grafo, &caminho, simetrica);
+            let aceita = !removidas.menor(adicionadas) || {
+                let delta = adicionadas.to_f64() - removidas.to_f64();
+                rng.gen::<f64>() < (-delta / temperatura).exp()
+            };
+            if aceita {
+                aplica(&mut caminho, &movimento);
+                fo = fo + adicionadas - removidas;
+                aceitos += 1;
+                if fo.menor(best.fo()) {
+                    best = Solucao::com_fo(caminho.clone(), fo);
+                    temperatura_melhor = temperatura;
+                    melhorou = true;
+                }
+            }
+            if let Resfriamento::LundyMees(beta) = resfriamento {
+                temperatura = temperatura / (1.0 + beta * temperatura);
+            }
+        }
+
+        if melhorou {
+            it_alvo = it;
+            reaquecimentos = 0;
+        }
+        let congelada = !melhorou && (aceitos as f64) < TAXA_MINIMA * patamar as f64;
+        match resfriamento {
+            // Cada reaquecimento seguido sem melhora dobra a temperatura
+            Resfriamento::Reaquecimento(_) if congelada => {
+                reaquecimentos += 1;
+                let fator = (1u64 << reaquecimentos.min(32)) as f64;
+                temperatura = (temperatura_melhor * fator).min(temperatura_maxima);
+            }
+            _ if congelada => break,
+            Resfriamento::Geometrico(alfa) |
+            Resfriamento::Reaquecimento(alfa) => temperatura *= alfa,
+            Resfriamento::LundyMees(_) => {}
+        }
+        it += 1;
+    }
+
+    Ok((best, it_alvo))
+}
+
+// Recozimento simulado com movimentos 2-opt e Or-opt sorteados, avaliados pela
+// variação de custo. Cada iteração é um patamar de temperatura; sem
+// reaquecimento, a busca para quando congela.
+pub struct Recozimento<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    max_iter: u64,
+    heuristica: Heuristica,
+    semente: Option<Caminho>,
+    resfriamento: Resfriamento,
+    temperatura: Option<f64>,
+    aceitacao_inicial: f64,
+    patamar: Option<usize>,
+}
+
+impl<'a, G: Instancia> Recozimento<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Recozimento<G> {
+        Recozimento {
+            grafo: grafo,
+            timeout: u64::MAX,
+            max_iter: 1000,
+            heuristica: Heuristica::VizinhoMaisProximo,
+            semente: None,
+            resfriamento: Resfriamento::Geometrico(0.95),
+            temperatura: None,
+            aceitacao_inicial: 0.5,
+            patamar: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Recozimento<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Número máximo de patamares
+    #[allow(dead_code)]
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Recozimento<'a, G> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn heuristica(&mut self, heuristica: Heuristica) -> &mut Recozimento<'a, G> {
+        self.heuristica = heuristica;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Recozimento<'a, G> {
+        self.semente = Some(caminho);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn resfriamento(&mut self, resfriamento: Resfriamento) -> &mut Recozimento<'a, G> {
+        self.resfriamento = resfriamento;
+        self
+    }
+
+    // Sem ela, a temperatura inicial vem de movimentos sorteados a partir do
+    // tour inicial
+    #[allow(dead_code)]
+    pub fn temperatura(&mut self, temperatura: f64) -> &mut Recozimento<'a, G> {
+        self.temperatura = Some(temperatura);
+        self
+    }
+
+    // Probabilidade, em (0, 1), de aceitar uma piora média na temperatura
+    // inicial automática
+    #[allow(dead_code)]
+    pub fn aceitacao_inicial(&mut self, aceitacao: f64) -> &mut Recozimento<'a, G> {
+        self.aceitacao_inicial = aceitacao;
+        self
+    }
+
+    // Movimentos por patamar; o padrão é 10 por vértice
+    #[allow(dead_code)]
+    pub fn patamar(&mut self, patamar: usize) -> &mut Recozimento<'a, G> {
+        self.patamar = Some(patamar);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.max_iter,
+              self.heuristica,
+              self.semente.as_ref(),
+              self.resfriamento,
+              self.temperatura,
+              self.aceitacao_inicial,
+              self.patamar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_permutacao, confere_qualidade, matriz_aleatoria};
+
+    #[test]
+    fn delta_dos_movimentos() {
+        let mut rng = rand::weak_rng();
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let assimetrica = matriz_aleatoria(12, false);
+
+        for &(g, simetrica) in &[(&g, true), (&assimetrica, false)] {
+            let mut caminho = (0..g.num_vertices()).collect::<Vec<_>>();
+            let mut fo = Solucao::new(g, caminho.clone()).fo();
+            for _ in 0..1000 {
+                let (movimento, adicionadas, removidas) =
+                    sorteia(&mut rng, g, &caminho, simetrica);
+                aplica(&mut caminho, &movimento);
+                fo = fo + adicionadas - removidas;
+                assert_eq!(fo, Solucao::new(g, caminho.clone()).fo());
+            }
+            confere_permutacao(&caminho, g.num_vertices());
+        }
+    }
+
+    #[test]
+    fn esquemas_de_resfriamento() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let esquemas = [Resfriamento::Geometrico(0.95),
+                        Resfriamento::LundyMees(1e-6),
+                        Resfriamento::Reaquecimento(0.9)];
+        for &resfriamento in &esquemas {
+            let (s, _) = Recozimento::new(&g)
+                .max_iter(300)
+                .resfriamento(resfriamento)
+                .solve()
+                .unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 15);
+        }
+    }
+
+    #[test]
+    fn temperatura_automatica() {
+        let mut rng = rand::weak_rng();
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let caminho = (0..48).collect::<Vec<_>>();
+        let alta = temperatura_inicial(&mut rng, &g, &caminho, true, 0.9);
+        let baixa = temperatura_inicial(&mut rng, &g, &caminho, true, 0.1);
+        assert!(alta > baixa && baixa > 0.0);
+        for &aceitacao in &[0.0, 1.0, 1.5] {
+            match Recozimento::new(&g).aceitacao_inicial(aceitacao).solve() {
+                Err(ErroConstrucao::ParametroInvalido(_)) => {}
+                _ => panic!("esperava aceitação inicial inválida: {}", aceitacao),
+            }
+        }
+        // Com temperatura explícita a aceitação inicial é ignorada
+        assert!(Recozimento::new(&g)
+            .max_iter(1)
+            .temperatura(100.0)
+            .aceitacao_inicial(1.5)
+            .solve()
+            .is_ok());
+        for &resfriamento in &[Resfriamento::Geometrico(1.0),
+                               Resfriamento::Geometrico(-0.5),
+                               Resfriamento::Reaquecimento(0.0),
+                               Resfriamento::LundyMees(0.0),
+                               Resfriamento::LundyMees(-1.0)] {
+            match Recozimento::new(&g).resfriamento(resfriamento).solve() {
+                Err(ErroConstrucao::ParametroInvalido(_)) => {}
+                _ => panic!("esperava resfriamento inválido: {:?}", resfriamento),
+            }
+        }
+
+        // Instância pequena demais para os movimentos
+        let g = Grafo::toy();
+        let (s, _) = Recozimento::new(&g).solve().unwrap();
+        assert_eq!(s.caminho().len(), g.num_vertices());
+    }
+}
@@ -0,0 +1,291 @@
+extern crate rand;
+
+use std::u64;
+use std::cmp::{min, max};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::{Duration, Instant};
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use construcao::{self, Heuristica};
+use diagnostico;
+use busca_local::two_opt_swap;
+use vizinhanca::{Avaliacao, Estrutura, DoisOpt};
+use erro::ErroConstrucao;
+
+// Iterações sem repetir uma solução antes de a tenure reativa diminuir
+const JANELA_REATIVA: u64 = 100;
+
+// Menor tenure da versão reativa
+const TENURE_MINIMA: usize = 2;
+
+fn aresta(a: Vertice, b: Vertice) -> (Vertice, Vertice) {
+    (min(a, b), max(a, b))
+}
+
+// Hash do ciclo independente do vértice inicial e, nas instâncias simétricas,
+// do sentido
+fn assinatura(caminho: &Caminho, simetrica: bool) -> u64 {
+    let n = caminho.len();
+    let inicio = caminho.iter().position(|&v| v == 0).unwrap();
+    let invertido = simetrica && caminho[(inicio + n - 1) % n] < caminho[(inicio + 1) % n];
+    let mut hasher = DefaultHasher::new();
+    for t in 0..n {
+        let i = if invertido { (inicio + n - t) % n } else { (inicio + t) % n };
+        hasher.write_usize(caminho[i]);
+    }
+    hasher.finish()
+}
+
+// Tenure reativa: cresce quando a busca revisita uma solução e diminui depois
+// de JANELA_REATIVA iterações sem repetição
+struct Tenure {
+    valor: usize,
+    maximo: usize,
+    reativa: bool,
+    visitadas: HashMap<u64, u64>,
+    ultima_mudanca: u64,
+}
+
+impl Tenure {
+    fn atualiza(&mut self, caminho: &Caminho, simetrica: bool, it: u64) {
+        if !self.reativa {
+            return;
+        }
+        let repetida = self.visitadas.insert(assinatura(caminho, simetrica), it).is_some();
+        if repetida {
+            self.valor = min(self.valor + self.valor / 5 + 1, self.maximo);
+            self.ultima_mudanca = it;
+        } else if it - self.ultima_mudanca > JANELA_REATIVA {
+            self.valor = max(self.valor * 9 / 10, TENURE_MINIMA);
+            self.ultima_mudanca = it;
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           timeout: Duration,
+                           max_iter: u64,
+                           heuristica: Heuristica,
+                           semente: Option<&Caminho>,
+                           tenure: usize,
+                           reativa: bool)
+                           -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let t = Instant::now();
+
+    let inicial = match semente {
+        Some(caminho) => Solucao::new(grafo, caminho.clone()),
+        None => construcao::constroi(&mut rand::weak_rng(), grafo, heuristica)?,
+    };
+    let n = inicial.caminho().len();
+    if n < 5 {
+        return Ok((inicial, 0));
+    }
+
+    let simetrica = diagnostico::simetrica(grafo);
+    let mut caminho = inicial.caminho().clone();
+    let mut fo = inicial.fo();
+    let mut best = inicial;
+    // Iteração até a qual cada aresta está fixa: se está no tour, foi
+    // adicionada e não pode sair; se não está, foi removida e não pode voltar
+    let mut tabu: HashMap<(Vertice, Vertice), u64> = HashMap::new();
+    let mut tenure = Tenure {
+        valor: tenure,
+        maximo: max(n / 2, tenure),
+        reativa: reativa,
+        visitadas: HashMap::new(),
+        ultima_mudanca: 0,
+    };
+
+    let mut it = 0;
+    let mut it_alvo = 0;
+    while it - it_alvo < max_iter && t.elapsed() < timeout {
+        let tour = Avaliacao::new(grafo, &caminho);
+        let mut escolhido: Option<(usize, usize, G::Peso)> = None;
+        {
+            let proibida = |a: Vertice, b: Vertice| {
+                tabu.get(&aresta(a, b)).map_or(false, |&ate| ate > it)
+            };
+
+            // Inverte caminho[i..k]; inversões de um vértice ou do tour quase
+            // inteiro não mudam o ciclo
+            for i in 0..n - 1 {
+                for k in (i + 2..n).filter(|&k| k - i < n - 1) {
+                    let (removidas, adicionadas) = DoisOpt.delta(grafo, &tour, (i, k));
+                    let nova_fo = fo + adicionadas - removidas;
+                    if let Some((_, _, melhor)) = escolhido {
+                        if !nova_fo.menor(melhor) {
+                            continue;
+                        }
+                    }
+                    // Aspiração: um movimento tabu que supera o melhor tour é aceito
+                    let (antes, depois) = (caminho[(i + n - 1) % n], caminho[k]);
+                    let (inicio, fim) = (caminho[i], caminho[k - 1]);
+                    let tabu = proibida(antes, fim) || proibida(inicio, depois) ||
+                               proibida(antes, inicio) ||
+                               proibida(fim, depois);
+                    if !tabu || nova_fo.menor(best.fo()) {
+                        escolhido = Some((i, k, nova_fo));
+                    }
+                }
+            }
+        }
+
+        if let Some((i, k, nova_fo)) = escolhido {
+            let (antes, depois) = (caminho[(i + n - 1) % n], caminho[k]);
+            let (inicio, fim) = (caminho[i], caminho[k - 1]);
+            let ate = it + tenure.valor as u64;
+            for &(a, b) in &[(antes, fim), (inicio, depois), (antes, inicio), (fim, depois)] {
+                tabu.insert(aresta(a, b), ate);
+            }
+            caminho = two_opt_swap(caminho, i, k);
+            fo = nova_fo;
+            tenure.atualiza(&caminho, simetrica, it);
+
+            if fo.menor(best.fo()) {
+                best = Solucao::com_fo(caminho.clone(), fo);
+                it_alvo = it;
+            }
+        }
+        // Descarta as arestas que já foram liberadas
+        if it % n as u64 == 0 {
+            tabu.retain(|_, &mut ate| ate > it);
+        }
+        it += 1;
+    }
+
+    Ok((best, it_alvo))
+}
+
+// Busca tabu na vizinhança 2-opt completa, com as arestas dos movimentos
+// recentes como atributos tabu. Serve também às instâncias assimétricas e às
+// com arestas proibidas, onde a busca atravessa tours piores ou infactíveis.
+pub struct Tabu<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    max_iter: u64,
+    heuristica: Heuristica,
+    semente: Option<Caminho>,
+    tenure: usize,
+    reativa: bool,
+}
+
+impl<'a, G: Instancia> Tabu<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Tabu<G> {
+        Tabu {
+            grafo: grafo,
+            timeout: u64::MAX,
+            max_iter: 200,
+            heuristica: Heuristica::VizinhoMaisProximo,
+            semente: None,
+            tenure: 10,
+            reativa: true,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Tabu<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Iterações sem melhorar a melhor solução antes de parar
+    #[allow(dead_code)]
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Tabu<'a, G> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn heuristica(&mut self, heuristica: Heuristica) -> &mut Tabu<'a, G> {
+        self.heuristica = heuristica;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Tabu<'a, G> {
+        self.semente = Some(caminho);
+        self
+    }
+
+    // Iterações em que as arestas de um movimento ficam fixas; na versão
+    // reativa, é só o valor inicial
+    #[allow(dead_code)]
+    pub fn tenure(&mut self, tenure: usize) -> &mut Tabu<'a, G> {
+        self.tenure = tenure;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn reativa(&mut self, reativa: bool) -> &mut Tabu<'a, G> {
+        self.reativa = reativa;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.max_iter,
+              self.heuristica,
+              self.semente.as_ref(),
+              self.tenure,
+              self.reativa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::rand::Rng;
+    use grafo::{Grafo, INF};
+    use testes::{OTIMO_ATT48, confere_qualidade};
+
+    #[test]
+    fn assinatura_do_ciclo() {
+        let a = vec![0, 1, 2, 3, 4];
+        let rotacao = vec![2, 3, 4, 0, 1];
+        let inverso = vec![0, 4, 3, 2, 1];
+        assert_eq!(assinatura(&a, true), assinatura(&rotacao, true));
+        assert_eq!(assinatura(&a, true), assinatura(&inverso, true));
+        assert!(assinatura(&a, false) != assinatura(&inverso, false));
+    }
+
+    #[test]
+    fn simetrica_com_e_sem_reacao() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        for &reativa in &[true, false] {
+            let (s, _) = Tabu::new(&g).max_iter(100).reativa(reativa).solve().unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 10);
+        }
+    }
+
+    // Ciclo barato escondido numa instância assimétrica, com arestas
+    // proibidas entre vértices distantes no ciclo
+    #[test]
+    fn assimetrica_restrita() {
+        let mut rng = super::rand::weak_rng();
+        let n = 20;
+        let mut ordem = (0..n).collect::<Vec<_>>();
+        rng.shuffle(&mut ordem);
+        let mut m = vec![vec![0; n]; n];
+        for a in 0..n {
+            for b in (0..n).filter(|&b| b != a) {
+                let salto = (b + n - a) % n;
+                m[ordem[a]][ordem[b]] = match salto {
+                    1 => 1,
+                    _ if salto <= 4 || salto >= n - 3 => rng.gen_range(10, 20),
+                    _ => INF,
+                };
+            }
+        }
+        let g = Grafo::from_matriz(m);
+        let (s, _) = Tabu::new(&g).max_iter(300).solve().unwrap();
+        assert_eq!(s.fo(), n as u64);
+    }
+}
@@ -0,0 +1,73 @@
+// Auxiliares compartilhados pelos testes dos solvers
+
+extern crate rand;
+
+use self::rand::Rng;
+use grafo::{Grafo, Solucao, Caminho, Vertice, Peso};
+use instancia::Instancia;
+use coordenadas::{Coordenadas, Metrica, Ponto};
+
+// Custo do tour ótimo da att48
+pub const OTIMO_ATT48: Peso = 10628;
+
+// Permutação sorteada dos vértices 0..n
+pub fn tour_aleatorio(n: usize) -> Caminho {
+    let mut caminho = (0..n).collect::<Vec<_>>();
+    rand::weak_rng().shuffle(&mut caminho);
+    caminho
+}
+
+pub fn solucao_aleatoria<G: Instancia>(grafo: &G) -> Solucao<G::Peso> {
+    Solucao::new(grafo, tour_aleatorio(grafo.num_vertices()))
+}
+
+// Pesos sorteados entre 1 e 99, simétricos ou não
+pub fn matriz_aleatoria(n: usize, simetrica: bool) -> Grafo {
+    let mut rng = rand::weak_rng();
+    let mut m = vec![vec![0; n]; n];
+    for i in 0..n {
+        for j in (0..n).filter(|&j| j != i) {
+            m[i][j] = if simetrica && j < i { m[j][i] } else { rng.gen_range(1, 100) };
+        }
+    }
+    Grafo::from_matriz(m)
+}
+
+// Pontos sorteados num quadrado de lado 10000, com distância euclidiana
+pub fn pontos_aleatorios(n: usize) -> Grafo {
+    let mut rng = rand::weak_rng();
+    let pontos = (0..n)
+        .map(|_| {
+            Ponto {
+                x: rng.gen_range(0.0, 10000.0),
+                y: rng.gen_range(0.0, 10000.0),
+                z: 0.0,
+            }
+        })
+        .collect();
+    Grafo::from_coordenadas(Coordenadas::new(pontos, Metrica::Euc2d))
+}
+
+// O caminho passa uma única vez por cada vértice 0..n
+pub fn confere_permutacao(caminho: &[Vertice], n: usize) {
+    let mut ordenado = caminho.to_vec();
+    ordenado.sort();
+    assert_eq!(ordenado, (0..n).collect::<Vec<_>>());
+}
+
+// A fo guardada na solução é o custo do seu tour
+pub fn confere_fo<G: Instancia>(grafo: &G, solucao: &Solucao<G::Peso>) {
+    assert_eq!(solucao.fo(), Solucao::new(grafo, solucao.caminho().clone()).fo());
+}
+
+// Além de conferir a fo, exige que ela fique a menos de pct% acima do ótimo
+pub fn confere_qualidade<G>(grafo: &G, solucao: &Solucao, otimo: Peso, pct: Peso)
+    where G: Instancia<Peso = Peso>
+{
+    confere_fo(grafo, solucao);
+    let fo = solucao.fo();
+    assert!(fo >= otimo && fo < otimo * (100 + pct) / 100,
+            "fo {} para o ótimo {}",
+            fo,
+            otimo);
+}
@@ -0,0 +1,498 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::fs::File;
+use grafo::{self, Grafo, Solucao, Caminho};
+use instancia::Instancia;
+use custo::Custo;
+use erro::{self, ErroInstancia};
+use coordenadas::{Coordenadas, Metrica, Ponto};
+
+// Acima desse tamanho as distâncias de instâncias com coordenadas são
+// calculadas sob demanda em vez de guardadas numa matriz
+const MAX_VERTICES_MATRIZ: usize = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TipoPeso {
+    Explicito,
+    Coordenadas(Metrica),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FormatoPeso {
+    FullMatrix,
+    UpperRow,
+    LowerRow,
+    UpperDiagRow,
+    LowerDiagRow,
+    UpperCol,
+    LowerCol,
+    UpperDiagCol,
+    LowerDiagCol,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cabecalho {
+    pub nome: String,
+    pub tipo: String,
+    pub comentario: String,
+    pub dimensao: usize,
+    pub tipo_peso: TipoPeso,
+    pub formato_peso: FormatoPeso,
+}
+
+impl Cabecalho {
+    fn new() -> Cabecalho {
+        Cabecalho {
+            nome: String::new(),
+            tipo: "TSP".to_string(),
+            comentario: String::new(),
+            dimensao: 0,
+            tipo_peso: TipoPeso::Explicito,
+            formato_peso: FormatoPeso::FullMatrix,
+        }
+    }
+}
+
+impl TipoPeso {
+    fn from_nome(s: &str) -> Option<TipoPeso> {
+        Some(match s {
+            "EXPLICIT" => TipoPeso::Explicito,
+            "EUC_2D" => TipoPeso::Coordenadas(Metrica::Euc2d),
+            "EUC_3D" => TipoPeso::Coordenadas(Metrica::Euc3d),
+            "CEIL_2D" => TipoPeso::Coordenadas(Metrica::Ceil2d),
+            "MAN_2D" => TipoPeso::Coordenadas(Metrica::Man2d),
+            "MAX_2D" => TipoPeso::Coordenadas(Metrica::Max2d),
+            "ATT" => TipoPeso::Coordenadas(Metrica::Att),
+            "GEO" => TipoPeso::Coordenadas(Metrica::Geo),
+            _ => return None,
+        })
+    }
+}
+
+impl FormatoPeso {
+    fn from_nome(s: &str) -> Option<FormatoPeso> {
+        Some(match s {
+            "FULL_MATRIX" => FormatoPeso::FullMatrix,
+            "UPPER_ROW" => FormatoPeso::UpperRow,
+            "LOWER_ROW" => FormatoPeso::LowerRow,
+            "UPPER_DIAG_ROW" => FormatoPeso::UpperDiagRow,
+            "LOWER_DIAG_ROW" => FormatoPeso::LowerDiagRow,
+            "UPPER_COL" => FormatoPeso::UpperCol,
+            "LOWER_COL" => FormatoPeso::LowerCol,
+            "UPPER_DIAG_COL" => FormatoPeso::UpperDiagCol,
+            "LOWER_DIAG_COL" => FormatoPeso::LowerDiagCol,
+            _ => return None,
+        })
+    }
+
+    // Posições (i, j) da matriz na ordem em que aparecem na EDGE_WEIGHT_SECTION.
+    // Os formatos por coluna são os formatos por linha do triângulo oposto.
+    fn posicoes(&self, n: usize) -> Vec<(usize, usize)> {
+        let mut pos = Vec::with_capacity(n * n);
+        for i in 0..n {
+            let colunas = match *self {
+                FormatoPeso::FullMatrix => 0..n,
+                FormatoPeso::UpperRow | FormatoPeso::LowerCol => i + 1..n,
+                FormatoPeso::LowerRow | FormatoPeso::UpperCol => 0..i,
+                FormatoPeso::UpperDiagRow | FormatoPeso::LowerDiagCol => i..n,
+                FormatoPeso::LowerDiagRow | FormatoPeso::UpperDiagCol => 0..i + 1,
+            };
+            for j in colunas {
+                pos.push((i, j));
+            }
+        }
+        pos
+    }
+}
+
+enum Secao {
+    Cabecalho,
+    Coordenadas,
+    Pesos,
+    Ignorada,
+}
+
+// Divide uma linha `CHAVE : VALOR`, devolvendo também a coluna onde o valor começa
+fn campo(linha: &str) -> (&str, &str, usize) {
+    match linha.find(':') {
+        Some(i) => {
+            let resto = &linha[i + 1..];
+            let valor = resto.trim();
+            let coluna = i + 2 + resto.len() - resto.trim_start().len();
+            (linha[..i].trim(), valor, coluna)
+        }
+        None => (linha.trim(), "", linha.len() + 1),
+    }
+}
+
+fn matriz_explicita<P: Custo>(cab: &Cabecalho, pesos: &[P]) -> Result<Vec<Vec<P>>, ErroInstancia> {
+    let n = cab.dimensao;
+    let posicoes = cab.formato_peso.posicoes(n);
+    if pesos.len() != posicoes.len() {
+        return Err(ErroInstancia::NumeroPesos {
+            esperado: posicoes.len(),
+            encontrado: pesos.len(),
+        });
+    }
+
+    let mut matriz = vec![vec![P::zero(); n]; n];
+    for (&(i, j), &peso) in posicoes.iter().zip(pesos) {
+        matriz[i][j] = peso;
+        if cab.formato_peso != FormatoPeso::FullMatrix {
+            matriz[j][i] = peso;
+        }
+    }
+    Ok(matriz)
+}
+
+fn grafo_coordenadas<P: Custo>(cab: &Cabecalho,
+                               pontos: Vec<Ponto>,
+                               metrica: Metrica)
+                               -> Result<Grafo<P>, ErroInstancia> {
+    if pontos.len() != cab.dimensao {
+        return Err(ErroInstancia::Dimensao {
+            declarada: cab.dimensao,
+            encontrada: pontos.len(),
+        });
+    }
+
+    let grafo = Grafo::from_coordenadas(Coordenadas::new(pontos, metrica));
+    if cab.dimensao <= MAX_VERTICES_MATRIZ {
+        Ok(grafo.materializa())
+    } else {
+        Ok(grafo)
+    }
+}
+
+// Com pesos reais, as distâncias das métricas não são arredondadas
+pub fn le<R, P>(leitor: R) -> Result<(Cabecalho, Grafo<P>), ErroInstancia>
+    where R: BufRead,
+          P: Custo
+{
+    let mut cab = Cabecalho::new();
+    let mut secao = Secao::Cabecalho;
+    let mut pontos = Vec::new();
+    let mut pesos = Vec::new();
+
+    for (i, linha) in leitor.lines().enumerate() {
+        let linha = linha?;
+        let num_linha = i + 1;
+        if linha.trim().is_empty() {
+            continue;
+        }
+
+        let (chave, valor, coluna_valor) = campo(&linha);
+        match chave {
+            "EOF" => break,
+            "NODE_COORD_SECTION" => {
+                secao = Secao::Coordenadas;
+                continue;
+            }
+            "EDGE_WEIGHT_SECTION" => {
+                secao = Secao::Pesos;
+                continue;
+            }
+            "DISPLAY_DATA_SECTION" | "FIXED_EDGES_SECTION" | "DEPOT_SECTION" |
+            "DEMAND_SECTION" | "TOUR_SECTION" => {
+                secao = Secao::Ignorada;
+                continue;
+            }
+            _ => {}
+        }
+
+        match secao {
+            Secao::Cabecalho => {
+                match chave {
+                    "NAME" => cab.nome = valor.to_string(),
+                    "TYPE" => cab.tipo = valor.to_string(),
+                    "COMMENT" => cab.comentario = valor.to_string(),
+                    "DIMENSION" => cab.dimensao = erro::parse(valor, num_linha, coluna_valor)?,
+                    "EDGE_WEIGHT_TYPE" => {
+                        cab.tipo_peso = TipoPeso::from_nome(valor).ok_or_else(|| {
+                                ErroInstancia::Formato(format!("unsupported EDGE_WEIGHT_TYPE: {}",
+                                                               valor))
+                            })?
+                    }
+                    "EDGE_WEIGHT_FORMAT" => {
+                        cab.formato_peso = FormatoPeso::from_nome(valor).ok_or_else(|| {
+                                ErroInstancia::Formato(format!("unsupported EDGE_WEIGHT_FORMAT: \
+                                                                {}",
+                                                               valor))
+                            })?
+                    }
+                    _ => {}
+                }
+            }
+            Secao::Coordenadas => {
+                let campos = erro::tokens(&linha);
+                if campos.len() < 3 {
+                    return Err(ErroInstancia::Formato(format!("line {}: expected node id \
+                                                               and coordinates",
+                                                              num_linha)));
+                }
+                let mut coords = [0.0; 3];
+                for (k, &(coluna, token)) in campos.iter().skip(1).take(3).enumerate() {
+                    coords[k] = erro::parse(token, num_linha, coluna)?;
+                }
+                pontos.push(Ponto {
+                    x: coords[0],
+                    y: coords[1],
+                    z: coords[2],
+                });
+            }
+            Secao::Pesos => {
+                for (coluna, token) in erro::tokens(&linha) {
+                    pesos.push(erro::parse(token, num_linha, coluna)?);
+                }
+            }
+            Secao::Ignorada => {}
+        }
+    }
+
+    if cab.dimensao == 0 && pontos.is_empty() && pesos.is_empty() {
+        return Err(ErroInstancia::Vazia);
+    }
+
+    let grafo = match cab.tipo_peso {
+        TipoPeso::Explicito => Grafo::from_matriz(matriz_explicita(&cab, &pesos)?),
+        TipoPeso::Coordenadas(metrica) => grafo_coordenadas(&cab, pontos, metrica)?,
+    };
+
+    Ok((cab, grafo))
+}
+
+pub fn from_arquivo<P: Custo>(file: &str) -> Result<(Cabecalho, Grafo<P>), ErroInstancia> {
+    let path = Path::new(file);
+    le(BufReader::new(File::open(&path)?))
+}
+
+pub fn escreve_tour<W, P>(escritor: &mut W, nome: &str, solucao: &Solucao<P>) -> io::Result<()>
+    where W: Write,
+          P: Custo
+{
+    writeln!(escritor, "NAME : {}", nome)?;
+    writeln!(escritor, "COMMENT : Length = {}", solucao.fo())?;
+    writeln!(escritor, "TYPE : TOUR")?;
+    writeln!(escritor, "DIMENSION : {}", solucao.caminho().len())?;
+    writeln!(escritor, "TOUR_SECTION")?;
+    for &vert in solucao.caminho() {
+        writeln!(escritor, "{}", vert + 1)?;
+    }
+    writeln!(escritor, "-1")?;
+    writeln!(escritor, "EOF")
+}
+
+pub fn salva_tour<P: Custo>(file: &str, nome: &str, solucao: &Solucao<P>) -> io::Result<()> {
+    let mut escritor = BufWriter::new(File::create(Path::new(file))?);
+    escreve_tour(&mut escritor, nome, solucao)
+}
+
+#[allow(dead_code)]
+pub fn le_tour<R: BufRead, G: Instancia>(leitor: R, grafo: &G) -> Result<Caminho, ErroInstancia> {
+    let mut dimensao = None;
+    let mut em_tour = false;
+    let mut caminho = Vec::with_capacity(grafo.num_vertices());
+
+    'linhas: for (i, linha) in leitor.lines().enumerate() {
+        let linha = linha?;
+        let num_linha = i + 1;
+        if linha.trim().is_empty() {
+            continue;
+        }
+
+        if !em_tour {
+            let (chave, valor, coluna_valor) = campo(&linha);
+            match chave {
+                "DIMENSION" => dimensao = Some(erro::parse(valor, num_linha, coluna_valor)?),
+                "TOUR_SECTION" => em_tour = true,
+                "EOF" => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        for (coluna, token) in erro::tokens(&linha) {
+            let vert: i64 = erro::parse(token, num_linha, coluna)?;
+            if vert == -1 {
+                break 'linhas;
+            }
+            if vert < 1 || vert as usize > grafo.num_vertices() {
+                return Err(ErroInstancia::Parse {
+                    linha: num_linha,
+                    coluna: coluna,
+                    token: token.to_string(),
+                });
+            }
+            caminho.push(vert as usize - 1);
+        }
+    }
+
+    if let Some(n) = dimensao {
+        if n != grafo.num_vertices() {
+            return Err(ErroInstancia::Dimensao {
+                declarada: n,
+                encontrada: grafo.num_vertices(),
+            });
+        }
+    }
+    if !grafo::is_factivel(&caminho, grafo.num_vertices()) {
+        return Err(ErroInstancia::Formato("tour is not a permutation of the graph's vertices"
+            .to_string()));
+    }
+
+    Ok(caminho)
+}
+
+#[allow(dead_code)]
+pub fn tour_from_arquivo<G: Instancia>(file: &str, grafo: &G) -> Result<Caminho, ErroInstancia> {
+    let path = Path::new(file);
+    le_tour(BufReader::new(File::open(&path)?), grafo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Peso;
+
+    fn tour(grafo: &Grafo, caminho: &[usize]) -> Peso {
+        Solucao::new(grafo, caminho.iter().map(|v| v - 1).collect()).fo()
+    }
+
+    #[test]
+    fn att48_otimo() {
+        let (cab, g) = from_arquivo("instances/att48.tsp").unwrap();
+        assert_eq!(cab.nome, "att48");
+        assert_eq!(cab.tipo_peso, TipoPeso::Coordenadas(Metrica::Att));
+        let c = [1, 8, 38, 31, 44, 18, 7, 28, 6, 37, 19, 27, 17, 43, 30, 36, 46, 33, 20, 47,
+                 21, 32, 39, 48, 5, 42, 24, 10, 45, 35, 4, 26, 2, 29, 34, 41, 16, 22, 3, 23,
+                 14, 25, 13, 11, 12, 15, 40, 9];
+        assert_eq!(tour(&g, &c), 10628);
+    }
+
+    #[test]
+    fn dantzig42_otimo() {
+        let (_, g) = from_arquivo("instances/dantzig42.tsp").unwrap();
+        let mut c = vec![1];
+        c.extend((2..43).rev());
+        assert_eq!(tour(&g, &c), 699);
+    }
+
+    #[test]
+    fn fri26_otimo() {
+        let (_, g) = from_arquivo("instances/fri26.tsp").unwrap();
+        let c = [1, 25, 24, 23, 26, 22, 21, 17, 18, 20, 19, 16, 11, 12, 13, 15, 14, 10, 9, 8,
+                 7, 5, 6, 4, 3, 2];
+        assert_eq!(tour(&g, &c), 937);
+    }
+
+    #[test]
+    fn gr17_otimo() {
+        let (cab, g) = from_arquivo("instances/gr17.tsp").unwrap();
+        assert_eq!(cab.dimensao, 17);
+        assert_eq!(cab.formato_peso, FormatoPeso::LowerDiagRow);
+        let c = [1, 4, 13, 7, 8, 6, 17, 14, 15, 3, 11, 10, 2, 5, 9, 12, 16];
+        assert_eq!(tour(&g, &c), 2085);
+    }
+
+    #[test]
+    fn burma14_geo() {
+        let texto = "NAME: burma14\nTYPE: TSP\nDIMENSION: 14\nEDGE_WEIGHT_TYPE: GEO\n\
+                     NODE_COORD_SECTION\n1 16.47 96.10\n2 16.47 94.44\n3 20.09 92.54\n\
+                     4 22.39 93.37\n5 25.23 97.24\n6 22.00 96.05\n7 20.47 97.02\n\
+                     8 17.20 96.29\n9 16.30 97.38\n10 14.05 98.12\n11 16.53 97.38\n\
+                     12 21.52 95.59\n13 19.41 97.13\n14 20.09 94.55\nEOF\n";
+        let (_, g) = le(texto.as_bytes()).unwrap();
+        let c = [1, 2, 14, 3, 4, 5, 6, 12, 7, 13, 8, 11, 9, 10];
+        assert_eq!(tour(&g, &c), 3323);
+    }
+
+    #[test]
+    fn att48_real() {
+        let (_, g) = from_arquivo::<f64>("instances/att48.tsp").unwrap();
+        let c = [1, 8, 38, 31, 44, 18, 7, 28, 6, 37, 19, 27, 17, 43, 30, 36, 46, 33, 20, 47,
+                 21, 32, 39, 48, 5, 42, 24, 10, 45, 35, 4, 26, 2, 29, 34, 41, 16, 22, 3, 23,
+                 14, 25, 13, 11, 12, 15, 40, 9];
+        let fo = Solucao::new(&g, c.iter().map(|v| v - 1).collect()).fo();
+        assert!((fo - 10601.1274).abs() < 1e-3);
+    }
+
+    #[test]
+    fn formatos_triangulares() {
+        let completa = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                        EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n\
+                        0 1 2\n1 0 3\n2 3 0\nEOF\n";
+        let upper = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                     EDGE_WEIGHT_FORMAT: UPPER_ROW\nEDGE_WEIGHT_SECTION\n1 2 3\nEOF\n";
+        let lower_col = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                         EDGE_WEIGHT_FORMAT: LOWER_COL\nEDGE_WEIGHT_SECTION\n1 2 3\nEOF\n";
+        let upper_diag_col = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                              EDGE_WEIGHT_FORMAT: UPPER_DIAG_COL\nEDGE_WEIGHT_SECTION\n\
+                              0 1 0 2 3 0\nEOF\n";
+
+        let (_, esperado) = le::<_, Peso>(completa.as_bytes()).unwrap();
+        for texto in &[upper, lower_col, upper_diag_col] {
+            let (_, g) = le::<_, Peso>(texto.as_bytes()).unwrap();
+            for i in 0..3 {
+                for j in 0..3 {
+                    assert_eq!(g.distancia(i, j), esperado.distancia(i, j));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tour_ida_e_volta() {
+        let g = Grafo::toy();
+        let s = Solucao::new(&g, vec![2, 0, 1, 3]);
+        let mut buf = Vec::new();
+        escreve_tour(&mut buf, "toy", &s).unwrap();
+
+        let texto = String::from_utf8(buf.clone()).unwrap();
+        assert!(texto.contains("TOUR_SECTION\n3\n1\n2\n4\n-1\n"));
+        assert_eq!(le_tour(&buf[..], &g).unwrap(), *s.caminho());
+    }
+
+    #[test]
+    fn tour_otimo_gr17() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let texto = "NAME : gr17.opt.tour\nTYPE : TOUR\nDIMENSION : 17\nTOUR_SECTION\n\
+                     1 4 13 7 8 6 17 14 15 3 11 10 2 5 9 12 16\n-1\nEOF\n";
+        let c = le_tour(texto.as_bytes(), &g).unwrap();
+        assert_eq!(Solucao::new(&g, c).fo(), 2085);
+    }
+
+    #[test]
+    fn tour_vertice_repetido() {
+        let texto = "TYPE : TOUR\nDIMENSION : 4\nTOUR_SECTION\n1 2 2 4\n-1\n";
+        assert!(le_tour(texto.as_bytes(), &Grafo::toy()).is_err());
+    }
+
+    #[test]
+    fn tour_dimensao_errada() {
+        let texto = "TYPE : TOUR\nDIMENSION : 5\nTOUR_SECTION\n1 2 3 4\n-1\n";
+        match le_tour(texto.as_bytes(), &Grafo::toy()) {
+            Err(ErroInstancia::Dimensao { declarada: 5, encontrada: 4 }) => {}
+            _ => panic!("esperava erro de dimensão"),
+        }
+    }
+
+    #[test]
+    fn erros_de_instancia() {
+        let coords = "DIMENSION : 3\nEDGE_WEIGHT_TYPE : EUC_2D\nNODE_COORD_SECTION\n\
+                      1 0 0\n2 3 4\nEOF\n";
+        match le::<_, Peso>(coords.as_bytes()) {
+            Err(ErroInstancia::Dimensao { declarada: 3, encontrada: 2 }) => {}
+            _ => panic!("esperava erro de dimensão"),
+        }
+
+        let pesos = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                     EDGE_WEIGHT_FORMAT: UPPER_ROW\nEDGE_WEIGHT_SECTION\n1 2 y\nEOF\n";
+        match le::<_, Peso>(pesos.as_bytes()) {
+            Err(ErroInstancia::Parse { linha: 5, coluna: 5, .. }) => {}
+            _ => panic!("esperava erro de parse"),
+        }
+
+        let tipo = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: XRAY1\n";
+        assert!(le::<_, Peso>(tipo.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,581 @@
+extern crate rand;
+
+use self::rand::{Rng, sample};
+use grafo::{Solucao, Caminho, Vertice};
+use instancia::Instancia;
+use custo::Custo;
+use busca_local::{Contexto, Religacao, acumulados, two_opt, two_opt_swap, or_opt, three_opt};
+use lin_kernighan::lin_kernighan;
+
+// Tour com os custos acumulados nos dois sentidos: qualquer movimento é
+// avaliado em O(1), também nas instâncias assimétricas
+pub struct Avaliacao<'a, P: Custo> {
+    caminho: &'a Caminho,
+    ida: Vec<P>,
+    volta: Vec<P>,
+}
+
+impl<'a, P: Custo> Avaliacao<'a, P> {
+    pub fn new<G>(grafo: &G, caminho: &'a Caminho) -> Avaliacao<'a, P>
+        where G: Instancia<Peso = P>
+    {
+        let (ida, volta) = acumulados(grafo, caminho);
+        Avaliacao {
+            caminho: caminho,
+            ida: ida,
+            volta: volta,
+        }
+    }
+
+    // Vértice na posição i, dando a volta no tour
+    fn v(&self, i: usize) -> Vertice {
+        self.caminho[i % self.caminho.len()]
+    }
+
+    // Custo das arestas internas ao trecho caminho[i..k], percorrido no
+    // sentido do tour ou ao contrário
+    fn interno(&self, i: usize, k: usize, invertido: bool) -> P {
+        if invertido {
+            self.volta[k - 1] - self.volta[i]
+        } else {
+            self.ida[k - 1] - self.ida[i]
+        }
+    }
+}
+
+// Estrutura de vizinhança do VND e do VNS: gera todos os movimentos de um
+// tour, avalia a variação de custo de cada um em O(1), aplica e sorteia
+// movimentos para a perturbação. Os movimentos são descritos por posições no
+// caminho.
+pub trait Estrutura {
+    type Movimento: Copy;
+
+    // Chama `f` com cada movimento de um tour de n vértices
+    fn gera<F: FnMut(Self::Movimento)>(&self, n: usize, f: F);
+
+    // Custo das arestas que saem e das que entram no tour
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           movimento: Self::Movimento)
+                           -> (G::Peso, G::Peso);
+
+    // Movimento sorteado, ou None se o tour é pequeno demais para a vizinhança
+    fn sorteia<R: Rng>(&self, rng: &mut R, n: usize) -> Option<Self::Movimento>;
+
+    fn aplica(&self, caminho: &Caminho, movimento: Self::Movimento) -> Caminho;
+
+    // Busca local até um ótimo local da vizinhança. Por padrão, a descida
+    // completa; as estruturas com busca especializada usam as listas de
+    // candidatos do contexto.
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           solucao: &Solucao<G::Peso>,
+                           _: &Contexto)
+                           -> Solucao<G::Peso>
+        where Self: Sized
+    {
+        descida_completa(grafo, self, solucao)
+    }
+}
+
+// Inverte caminho[i..k]
+pub struct DoisOpt;
+
+// Troca os vértices das posições i < j
+pub struct Troca;
+
+// Tira o vértice da posição i e o reinsere para que fique na posição j
+pub struct Insercao;
+
+// Move o trecho caminho[i..i + tamanho], de até 3 vértices, para logo depois
+// do vértice da posição j, invertido ou não
+pub struct OrOpt;
+
+// Corta as arestas antes das posições i < j < k e religa os trechos
+// caminho[i..j] e caminho[j..k] de uma das quatro formas do 3-opt puro
+pub struct TresOpt;
+
+// Lin-Kernighan, ou o or3opt nas instâncias assimétricas. Os movimentos são
+// os do 3-opt, que o Lin-Kernighan encadeia na descida.
+pub struct LinKernighan;
+
+impl Estrutura for DoisOpt {
+    type Movimento = (usize, usize);
+
+    fn gera<F: FnMut((usize, usize))>(&self, n: usize, mut f: F) {
+        if n < 3 {
+            return;
+        }
+        // Inverter um vértice só não muda o tour
+        for i in 0..n - 1 {
+            for k in i + 2..n {
+                f((i, k));
+            }
+        }
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           (i, k): (usize, usize))
+                           -> (G::Peso, G::Peso) {
+        let n = tour.caminho.len();
+        let (antes, depois) = (tour.v(i + n - 1), tour.v(k));
+        let (inicio, fim) = (tour.v(i), tour.v(k - 1));
+        let removidas = grafo.distancia(antes, inicio) + tour.interno(i, k, false) +
+                        grafo.distancia(fim, depois);
+        let adicionadas = grafo.distancia(antes, fim) + tour.interno(i, k, true) +
+                          grafo.distancia(inicio, depois);
+        (removidas, adicionadas)
+    }
+
+    fn sorteia<R: Rng>(&self, rng: &mut R, n: usize) -> Option<(usize, usize)> {
+        if n < 4 {
+            return None;
+        }
+        let i = rng.gen_range(0, n - 2);
+        let k = rng.gen_range(i + 2, n);
+        Some((i, k))
+    }
+
+    fn aplica(&self, caminho: &Caminho, (i, k): (usize, usize)) -> Caminho {
+        two_opt_swap(caminho.clone(), i, k)
+    }
+
+    // Com listas de candidatos, a versão com don't-look bits; sem elas, a
+    // descida completa, que também serve às instâncias assimétricas
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           solucao: &Solucao<G::Peso>,
+                           contexto: &Contexto)
+                           -> Solucao<G::Peso> {
+        match contexto.candidatos() {
+            Some(candidatos) => two_opt(grafo, solucao, candidatos),
+            None => descida_completa(grafo, self, solucao),
+        }
+    }
+}
+
+impl Estrutura for Troca {
+    type Movimento = (usize, usize);
+
+    fn gera<F: FnMut((usize, usize))>(&self, n: usize, mut f: F) {
+        if n < 4 {
+            return;
+        }
+        for i in 0..n - 1 {
+            for j in i + 1..n {
+                f((i, j));
+            }
+        }
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           (i, j): (usize, usize))
+                           -> (G::Peso, G::Peso) {
+        let n = tour.caminho.len();
+        let d = |a, b| grafo.distancia(a, b);
+        let (x, y) = (tour.v(i), tour.v(j));
+        if j == i + 1 || (i == 0 && j == n - 1) {
+            // Vizinhos no tour: a x y b vira a y x b
+            let (p, q) = if j == i + 1 { (i, j) } else { (j, i) };
+            let (x, y) = (tour.v(p), tour.v(q));
+            let (a, b) = (tour.v(p + n - 1), tour.v(q + 1));
+            (d(a, x) + d(x, y) + d(y, b), d(a, y) + d(y, x) + d(x, b))
+        } else {
+            let (ai, bi) = (tour.v(i + n - 1), tour.v(i + 1));
+            let (aj, bj) = (tour.v(j - 1), tour.v(j + 1));
+            (d(ai, x) + d(x, bi) + d(aj, y) + d(y, bj), d(ai, y) + d(y, bi) + d(aj, x) + d(x, bj))
+        }
+    }
+
+    fn sorteia<R: Rng>(&self, rng: &mut R, n: usize) -> Option<(usize, usize)> {
+        if n < 4 {
+            return None;
+        }
+        let mut posicoes = sample(rng, 0..n, 2);
+        posicoes.sort();
+        Some((posicoes[0], posicoes[1]))
+    }
+
+    fn aplica(&self, caminho: &Caminho, (i, j): (usize, usize)) -> Caminho {
+        let mut novo = caminho.clone();
+        novo.swap(i, j);
+        novo
+    }
+}
+
+// Levar o primeiro vértice para o fim, ou o último para o início, só gira o
+// tour
+fn rotacao(n: usize, i: usize, j: usize) -> bool {
+    (i == 0 && j == n - 1) || (i == n - 1 && j == 0)
+}
+
+impl Estrutura for Insercao {
+    type Movimento = (usize, usize);
+
+    fn gera<F: FnMut((usize, usize))>(&self, n: usize, mut f: F) {
+        if n < 4 {
+            return;
+        }
+        for i in 0..n {
+            for j in (0..n).filter(|&j| j != i && !rotacao(n, i, j)) {
+                f((i, j));
+            }
+        }
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           (i, j): (usize, usize))
+                           -> (G::Peso, G::Peso) {
+        let n = tour.caminho.len();
+        let d = |a, b| grafo.distancia(a, b);
+        let (a, x, b) = (tour.v(i + n - 1), tour.v(i), tour.v(i + 1));
+        // Aresta (p, q) que recebe o vértice
+        let (p, q) = if j > i {
+            (tour.v(j), tour.v(j + 1))
+        } else {
+            (tour.v(j + n - 1), tour.v(j))
+        };
+        (d(a, x) + d(x, b) + d(p, q), d(a, b) + d(p, x) + d(x, q))
+    }
+
+    fn sorteia<R: Rng>(&self, rng: &mut R, n: usize) -> Option<(usize, usize)> {
+        if n < 4 {
+            return None;
+        }
+        loop {
+            let (i, j) = (rng.gen_range(0, n), rng.gen_range(0, n));
+            if i != j && !rotacao(n, i, j) {
+                return Some((i, j));
+            }
+        }
+    }
+
+    fn aplica(&self, caminho: &Caminho, (i, j): (usize, usize)) -> Caminho {
+        let mut novo = caminho.clone();
+        let v = novo.remove(i);
+        novo.insert(j, v);
+        novo
+    }
+}
+
+// O Or-opt só move trechos de até 3 vértices, e não para a aresta de onde
+// saíram
+fn destino_or_opt(n: usize, i: usize, tamanho: usize, j: usize) -> bool {
+    (j < i || j >= i + tamanho) && j != (i + n - 1) % n
+}
+
+impl Estrutura for OrOpt {
+    type Movimento = (usize, usize, usize, bool);
+
+    fn gera<F: FnMut((usize, usize, usize, bool))>(&self, n: usize, mut f: F) {
+        for tamanho in (1..4).filter(|&t| t + 3 <= n) {
+            for i in 0..n - tamanho + 1 {
+                for j in (0..n).filter(|&j| destino_or_opt(n, i, tamanho, j)) {
+                    f((i, tamanho, j, false));
+                    if tamanho > 1 {
+                        f((i, tamanho, j, true));
+                    }
+                }
+            }
+        }
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           (i, tamanho, j, invertido): (usize, usize, usize, bool))
+                           -> (G::Peso, G::Peso) {
+        let n = tour.caminho.len();
+        let d = |a, b| grafo.distancia(a, b);
+        let k = i + tamanho;
+        let (primeiro, ultimo) = (tour.v(i), tour.v(k - 1));
+        let (a, b) = (tour.v(i + n - 1), tour.v(k));
+        let (p, q) = (tour.v(j), tour.v(j + 1));
+        let removidas = d(a, primeiro) + tour.interno(i, k, false) + d(ultimo, b) + d(p, q);
+        let ligacoes = if invertido {
+            d(p, ultimo) + d(primeiro, q)
+        } else {
+            d(p, primeiro) + d(ultimo, q)
+        };
+        (removidas, d(a, b) + tour.interno(i, k, invertido) + ligacoes)
+    }
+
+    fn sorteia<R: Rng>(&self, rng: &mut R, n: usize) -> Option<(usize, usize, usize, bool)> {
+        if n < 4 {
+            return None;
+        }
+        let tamanho = rng.gen_range(1, (n - 3).min(3) + 1);
+        let i = rng.gen_range(0, n - tamanho + 1);
+        loop {
+            let j = rng.gen_range(0, n);
+            if destino_or_opt(n, i, tamanho, j) {
+                return Some((i, tamanho, j, tamanho > 1 && rng.gen()));
+            }
+        }
+    }
+
+    fn aplica(&self,
+              caminho: &Caminho,
+              (i, tamanho, j, invertido): (usize, usize, usize, bool))
+              -> Caminho {
+        let mut trecho = caminho[i..i + tamanho].to_vec();
+        if invertido {
+            trecho.reverse();
+        }
+        let p = caminho[j];
+        let mut novo = Vec::with_capacity(caminho.len());
+        for &v in caminho[..i].iter().chain(&caminho[i + tamanho..]) {
+            novo.push(v);
+            if v == p {
+                novo.extend(&trecho);
+            }
+        }
+        novo
+    }
+
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           solucao: &Solucao<G::Peso>,
+                           contexto: &Contexto)
+                           -> Solucao<G::Peso> {
+        or_opt(grafo, solucao, contexto.candidatos())
+    }
+}
+
+const RELIGACOES_3OPT: [Religacao; 4] =
+    [Religacao::S2S1, Religacao::S2S1i, Religacao::S2iS1, Religacao::S1iS2i];
+
+impl Estrutura for TresOpt {
+    type Movimento = (usize, usize, usize, Religacao);
+
+    fn gera<F: FnMut((usize, usize, usize, Religacao))>(&self, n: usize, mut f: F) {
+        if n < 5 {
+            return;
+        }
+        for i in 1..n - 1 {
+            for j in i + 1..n {
+                for k in j + 1..n + 1 {
+                    for &religacao in &RELIGACOES_3OPT {
+                        f((i, j, k, religacao));
+                    }
+                }
+            }
+        }
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           (i, j, k, religacao): (usize, usize, usize, Religacao))
+                           -> (G::Peso, G::Peso) {
+        let d = |a, b| grafo.distancia(a, b);
+        let (a, b) = (tour.v(i - 1), tour.v(k));
+        let removidas = d(a, tour.v(i)) + tour.interno(i, j, false) +
+                        d(tour.v(j - 1), tour.v(j)) + tour.interno(j, k, false) +
+                        d(tour.v(k - 1), b);
+
+        // Percorre os dois trechos na nova ordem, de a até b
+        let mut adicionadas = G::Peso::zero();
+        let mut anterior = a;
+        for &((de, ate), invertido) in &religacao.partes((i, j), (j, k)) {
+            let (primeiro, ultimo) = if invertido { (ate - 1, de) } else { (de, ate - 1) };
+            adicionadas = adicionadas + d(anterior, tour.v(primeiro)) +
+                          tour.interno(de, ate, invertido);
+            anterior = tour.v(ultimo);
+        }
+        (removidas, adicionadas + d(anterior, b))
+    }
+
+    fn sorteia<R: Rng>(&self,
+                       rng: &mut R,
+                       n: usize)
+                       -> Option<(usize, usize, usize, Religacao)> {
+        if n < 5 {
+            return None;
+        }
+        let mut cortes = sample(rng, 1..n + 1, 3);
+        cortes.sort();
+        let religacao = RELIGACOES_3OPT[rng.gen_range(0, RELIGACOES_3OPT.len())];
+        Some((cortes[0], cortes[1], cortes[2], religacao))
+    }
+
+    fn aplica(&self,
+              caminho: &Caminho,
+              (i, j, k, religacao): (usize, usize, usize, Religacao))
+              -> Caminho {
+        let mut novo = Vec::with_capacity(caminho.len());
+        novo.extend(&caminho[..i]);
+        for &((de, ate), invertido) in &religacao.partes((i, j), (j, k)) {
+            if invertido {
+                novo.extend(caminho[de..ate].iter().rev());
+            } else {
+                novo.extend(&caminho[de..ate]);
+            }
+        }
+        novo.extend(&caminho[k..]);
+        novo
+    }
+
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           solucao: &Solucao<G::Peso>,
+                           contexto: &Contexto)
+                           -> Solucao<G::Peso> {
+        three_opt(grafo, solucao, contexto.listas(), contexto.simetrica())
+    }
+}
+
+impl Estrutura for LinKernighan {
+    type Movimento = (usize, usize, usize, Religacao);
+
+    fn gera<F: FnMut((usize, usize, usize, Religacao))>(&self, n: usize, f: F) {
+        TresOpt.gera(n, f)
+    }
+
+    fn delta<G: Instancia>(&self,
+                           grafo: &G,
+                           tour: &Avaliacao<G::Peso>,
+                           movimento: (usize, usize, usize, Religacao))
+                           -> (G::Peso, G::Peso) {
+        TresOpt.delta(grafo, tour, movimento)
+    }
+
+    fn sorteia<R: Rng>(&self,
+                       rng: &mut R,
+                       n: usize)
+                       -> Option<(usize, usize, usize, Religacao)> {
+        TresOpt.sorteia(rng, n)
+    }
+
+    fn aplica(&self, caminho: &Caminho, movimento: (usize, usize, usize, Religacao)) -> Caminho {
+        TresOpt.aplica(caminho, movimento)
+    }
+
+    fn desce<G: Instancia>(&self,
+                           grafo: &G,
+                           solucao: &Solucao<G::Peso>,
+                           contexto: &Contexto)
+                           -> Solucao<G::Peso> {
+        if contexto.simetrica() {
+            lin_kernighan(grafo, solucao, contexto.listas())
+        } else {
+            three_opt(grafo, solucao, contexto.listas(), false)
+        }
+    }
+}
+
+// Movimento de maior ganho da vizinhança, com as arestas que saem e entram,
+// ou None num ótimo local
+pub fn melhor_movimento<G, E>(grafo: &G,
+                              estrutura: &E,
+                              caminho: &Caminho)
+                              -> Option<(E::Movimento, G::Peso, G::Peso)>
+    where G: Instancia,
+          E: Estrutura
+{
+    let tour = Avaliacao::new(grafo, caminho);
+    let mut melhor: Option<(E::Movimento, G::Peso, G::Peso)> = None;
+    estrutura.gera(caminho.len(), |movimento| {
+        let (removidas, adicionadas) = estrutura.delta(grafo, &tour, movimento);
+        if !adicionadas.menor(removidas) {
+            return;
+        }
+        let troca = match melhor {
+            None => true,
+            Some((_, r, a)) => (adicionadas + r).menor(a + removidas),
+        };
+        if troca {
+            melhor = Some((movimento, removidas, adicionadas));
+        }
+    });
+    melhor
+}
+
+// Descida best-improvement até um ótimo local da vizinhança. Cada passo
+// examina a vizinhança inteira.
+pub fn descida_completa<G, E>(grafo: &G,
+                              estrutura: &E,
+                              solucao: &Solucao<G::Peso>)
+                              -> Solucao<G::Peso>
+    where G: Instancia,
+          E: Estrutura
+{
+    let mut caminho = solucao.caminho().clone();
+    let mut fo = solucao.fo();
+    while let Some((movimento, removidas, adicionadas)) =
+        melhor_movimento(grafo, estrutura, &caminho) {
+        caminho = estrutura.aplica(&caminho, movimento);
+        fo = fo + adicionadas - removidas;
+    }
+    Solucao::com_fo(caminho, fo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{confere_fo, confere_permutacao, matriz_aleatoria, solucao_aleatoria,
+                 tour_aleatorio};
+    use busca_local::Vizinhanca;
+
+    // Cada movimento gerado e cada movimento sorteado dá uma permutação cuja
+    // fo é a fo anterior mais a variação calculada
+    fn confere<E: Estrutura>(estrutura: &E) {
+        let mut rng = rand::weak_rng();
+        for &simetrica in &[true, false] {
+            for n in 5..10 {
+                let g = matriz_aleatoria(n, simetrica);
+                let caminho = tour_aleatorio(n);
+                let s = Solucao::new(&g, caminho.clone());
+                let tour = Avaliacao::new(&g, &caminho);
+
+                let mut movimentos = vec![];
+                estrutura.gera(n, |m| movimentos.push(m));
+                assert!(!movimentos.is_empty());
+                movimentos.extend((0..20).map(|_| estrutura.sorteia(&mut rng, n).unwrap()));
+                for m in movimentos {
+                    let novo = estrutura.aplica(&caminho, m);
+                    let (removidas, adicionadas) = estrutura.delta(&g, &tour, m);
+                    assert_eq!(s.fo() + adicionadas - removidas,
+                               Solucao::new(&g, novo.clone()).fo());
+                    confere_permutacao(&novo, n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn delta_das_estruturas() {
+        confere(&DoisOpt);
+        confere(&Troca);
+        confere(&Insercao);
+        confere(&OrOpt);
+        confere(&TresOpt);
+    }
+
+    #[test]
+    fn descida_nas_estruturas() {
+        let g: Grafo = Grafo::from_tsplib("instances/gr17.tsp").unwrap();
+        let contexto = Contexto::new(&g, &[Vizinhanca::ThreeOpt], None);
+        let inicial = solucao_aleatoria(&g);
+        let s = DoisOpt.desce(&g, &inicial, &contexto);
+        confere_fo(&g, &s);
+        assert!(melhor_movimento(&g, &DoisOpt, s.caminho()).is_none());
+        // O 3-opt continua do ótimo local do 2-opt
+        let t = TresOpt.desce(&g, &s, &contexto);
+        assert!(t.fo() <= s.fo() && t.fo() >= 2085);
+        confere_fo(&g, &t);
+        for solucao in &[Troca.desce(&g, &inicial, &contexto),
+                         Insercao.desce(&g, &inicial, &contexto)] {
+            confere_fo(&g, solucao);
+            assert!(solucao.fo() <= inicial.fo());
+        }
+    }
+}
@@ -0,0 +1,234 @@
+extern crate rand;
+
+use std::u64;
+use std::time::{Duration, Instant};
+use self::rand::Rng;
+use grafo::{Solucao, Caminho};
+use instancia::Instancia;
+use custo::Custo;
+use construcao::{self, Heuristica};
+use diagnostico;
+use busca_local::{Vnd, Vizinhanca};
+use vizinhanca::{Estrutura, DoisOpt, Troca, Insercao, OrOpt, TresOpt, LinKernighan};
+use candidatos::TipoCandidatos;
+use erro::ErroConstrucao;
+
+fn aleatorios<R, E>(rng: &mut R, estrutura: &E, caminho: &Caminho, k: usize) -> Caminho
+    where R: Rng,
+          E: Estrutura
+{
+    let mut caminho = caminho.clone();
+    for _ in 0..k {
+        if let Some(movimento) = estrutura.sorteia(rng, caminho.len()) {
+            caminho = estrutura.aplica(&caminho, movimento);
+        }
+    }
+    caminho
+}
+
+// Perturbação na k-ésima vizinhança: k movimentos sorteados da estrutura
+// escolhida
+fn perturba<R, G>(rng: &mut R,
+                  grafo: &G,
+                  solucao: &Solucao<G::Peso>,
+                  perturbacao: Vizinhanca,
+                  k: usize)
+                  -> Solucao<G::Peso>
+    where R: Rng,
+          G: Instancia
+{
+    let caminho = solucao.caminho();
+    let novo = match perturbacao {
+        Vizinhanca::TwoOpt => aleatorios(rng, &DoisOpt, caminho, k),
+        Vizinhanca::Troca => aleatorios(rng, &Troca, caminho, k),
+        Vizinhanca::Insercao => aleatorios(rng, &Insercao, caminho, k),
+        Vizinhanca::OrOpt => aleatorios(rng, &OrOpt, caminho, k),
+        Vizinhanca::ThreeOpt => aleatorios(rng, &TresOpt, caminho, k),
+        Vizinhanca::LinKernighan => aleatorios(rng, &LinKernighan, caminho, k),
+    };
+    Solucao::new(grafo, novo)
+}
+
+#[allow(dead_code)]
+pub fn solve<G: Instancia>(grafo: &G,
+                           timeout: Duration,
+                           max_iter: u64,
+                           heuristica: Heuristica,
+                           semente: Option<&Caminho>,
+                           candidatos: Option<(TipoCandidatos, usize)>,
+                           vizinhancas: &[Vizinhanca],
+                           perturbacao: Vizinhanca,
+                           k_max: usize)
+                           -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+    diagnostico::verifica_hamiltoniano(grafo)?;
+    let mut rng = rand::weak_rng();
+    let t = Instant::now();
+    let vnd = Vnd::new(grafo, vizinhancas, candidatos);
+
+    let inicial = match semente {
+        Some(caminho) => Solucao::new(grafo, caminho.clone()),
+        None => construcao::constroi(&mut rng, grafo, heuristica)?,
+    };
+    let mut best = vnd.aplica(grafo, &inicial);
+
+    let mut it = 0;
+    let mut it_alvo = 0;
+    while it - it_alvo < max_iter && t.elapsed() < timeout {
+        // Perturbações cada vez maiores até uma delas levar a um tour melhor,
+        // que recomeça da menor
+        let mut k = 1;
+        while k <= k_max && t.elapsed() < timeout {
+            let perturbada = perturba(&mut rng, grafo, &best, perturbacao, k);
+            let vizinho = vnd.aplica(grafo, &perturbada);
+            if vizinho.fo().menor(best.fo()) {
+                best = vizinho;
+                it_alvo = it;
+                k = 1;
+            } else {
+                k += 1;
+            }
+        }
+        it += 1;
+    }
+
+    Ok((best, it_alvo))
+}
+
+// VNS geral: perturbação em vizinhanças de tamanho crescente seguida do VND
+// nas vizinhanças configuradas
+pub struct Vns<'a, G: Instancia + 'a> {
+    grafo: &'a G,
+    timeout: u64,
+    max_iter: u64,
+    heuristica: Heuristica,
+    semente: Option<Caminho>,
+    candidatos: Option<(TipoCandidatos, usize)>,
+    vizinhancas: Vec<Vizinhanca>,
+    perturbacao: Vizinhanca,
+    k_max: usize,
+}
+
+impl<'a, G: Instancia> Vns<'a, G> {
+    #[allow(dead_code)]
+    pub fn new(grafo: &G) -> Vns<G> {
+        Vns {
+            grafo: grafo,
+            timeout: u64::MAX,
+            max_iter: 50,
+            heuristica: Heuristica::VizinhoMaisProximo,
+            semente: None,
+            candidatos: None,
+            vizinhancas: vec![Vizinhanca::TwoOpt, Vizinhanca::OrOpt],
+            perturbacao: Vizinhanca::TwoOpt,
+            k_max: 5,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Vns<'a, G> {
+        self.timeout = timeout;
+        self
+    }
+
+    // Ciclos de perturbação (de 1 a k_max) sem melhorar a melhor solução antes
+    // de parar
+    #[allow(dead_code)]
+    pub fn max_iter(&mut self, max_iter: u64) -> &mut Vns<'a, G> {
+        self.max_iter = max_iter;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn heuristica(&mut self, heuristica: Heuristica) -> &mut Vns<'a, G> {
+        self.heuristica = heuristica;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn semente(&mut self, caminho: Caminho) -> &mut Vns<'a, G> {
+        self.semente = Some(caminho);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn candidatos(&mut self, k: usize) -> &mut Vns<'a, G> {
+        self.candidatos = Some((TipoCandidatos::MaisProximos, k));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn candidatos_quadrantes(&mut self, k: usize) -> &mut Vns<'a, G> {
+        self.candidatos = Some((TipoCandidatos::Quadrantes, k));
+        self
+    }
+
+    // Vizinhanças do VND, na ordem em que são exploradas
+    #[allow(dead_code)]
+    pub fn vizinhancas(&mut self, vizinhancas: Vec<Vizinhanca>) -> &mut Vns<'a, G> {
+        self.vizinhancas = vizinhancas;
+        self
+    }
+
+    // Estrutura dos movimentos sorteados na perturbação
+    #[allow(dead_code)]
+    pub fn perturbacao(&mut self, perturbacao: Vizinhanca) -> &mut Vns<'a, G> {
+        self.perturbacao = perturbacao;
+        self
+    }
+
+    // Maior número de movimentos sorteados numa perturbação
+    #[allow(dead_code)]
+    pub fn k_max(&mut self, k_max: usize) -> &mut Vns<'a, G> {
+        self.k_max = k_max;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn solve(&self) -> Result<(Solucao<G::Peso>, u64), ErroConstrucao> {
+        solve(self.grafo,
+              Duration::from_secs(self.timeout),
+              self.max_iter,
+              self.heuristica,
+              self.semente.as_ref(),
+              self.candidatos,
+              &self.vizinhancas,
+              self.perturbacao,
+              self.k_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grafo::Grafo;
+    use testes::{OTIMO_ATT48, confere_qualidade};
+
+    #[test]
+    fn perturbacoes_de_todas_as_estruturas() {
+        let g: Grafo = Grafo::from_tsplib("instances/att48.tsp").unwrap();
+        let perturbacoes = [Vizinhanca::TwoOpt,
+                            Vizinhanca::Troca,
+                            Vizinhanca::Insercao,
+                            Vizinhanca::OrOpt,
+                            Vizinhanca::ThreeOpt];
+        for &perturbacao in &perturbacoes {
+            let (s, _) = Vns::new(&g).max_iter(10).perturbacao(perturbacao).solve().unwrap();
+            confere_qualidade(&g, &s, OTIMO_ATT48, 10);
+        }
+    }
+
+    #[test]
+    fn vnd_com_troca_e_insercao() {
+        let g: Grafo = Grafo::from_arquivo("instances/p01.txt").unwrap();
+        let (s, _) = Vns::new(&g)
+            .max_iter(20)
+            .vizinhancas(vec![Vizinhanca::Troca, Vizinhanca::Insercao, Vizinhanca::TwoOpt])
+            .solve()
+            .unwrap();
+        assert_eq!(s.fo(), 291);
+
+        let g = Grafo::toy();
+        let (s, _) = Vns::new(&g).max_iter(5).solve().unwrap();
+        assert_eq!(s.fo(), 8);
+    }
+}